@@ -0,0 +1,180 @@
+//! Shadow tables holding a copy of a sheet, for `xl0(cache=1)`.
+
+use calamine::Data;
+use sqlite_loadable::ext::{
+    sqlite3ext_bind_blob, sqlite3ext_bind_double, sqlite3ext_bind_int64, sqlite3ext_bind_null,
+    sqlite3ext_bind_text, sqlite3ext_column_value, sqlite3ext_errmsg, sqlite3ext_finalize,
+    sqlite3ext_prepare_v2, sqlite3ext_step,
+};
+use sqlite_loadable::prelude::*;
+use sqlite_loadable::{api, Error, Result};
+use std::ffi::{c_void, CStr, CString};
+use std::os::raw::c_int;
+
+const SQLITE_OK: c_int = 0;
+const SQLITE_ROW: c_int = 100;
+const SQLITE_DONE: c_int = 101;
+
+/// Largest number of bound parameters used in a single INSERT.
+const MAX_PARAMETERS: usize = 999;
+
+/// `SQLITE_TRANSIENT`: SQLite copies bound text and blobs before returning.
+fn transient() -> Option<unsafe extern "C" fn(*mut c_void)> {
+    Some(unsafe { std::mem::transmute::<isize, unsafe extern "C" fn(*mut c_void)>(-1) })
+}
+
+fn last_error(db: *mut sqlite3) -> String {
+    unsafe {
+        let msg = sqlite3ext_errmsg(db);
+        if msg.is_null() {
+            "unknown error".to_owned()
+        } else {
+            CStr::from_ptr(msg).to_string_lossy().into_owned()
+        }
+    }
+}
+
+/// Quote an identifier for use in SQL, e.g. `my "table"` → `"my ""table"""`.
+pub fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// Quote a table name that may be qualified with a schema, like `main.t`.
+pub fn quote_table_name(name: &str) -> String {
+    match name.split_once('.') {
+        Some((schema, table)) => format!("{}.{}", quote_identifier(schema), quote_identifier(table)),
+        None => quote_identifier(name),
+    }
+}
+
+/// A prepared statement, finalized on drop.
+pub struct Statement {
+    db: *mut sqlite3,
+    stmt: *mut sqlite3_stmt,
+}
+
+impl Statement {
+    pub fn prepare(db: *mut sqlite3, sql: &str) -> Result<Statement> {
+        let c_sql = CString::new(sql).map_err(|_| Error::new_message("SQL contains a NUL byte"))?;
+        let mut stmt: *mut sqlite3_stmt = std::ptr::null_mut();
+        let rc = unsafe {
+            sqlite3ext_prepare_v2(db, c_sql.as_ptr(), -1, &mut stmt, std::ptr::null_mut())
+        };
+        if rc != SQLITE_OK {
+            return Err(Error::new_message(last_error(db)));
+        }
+        Ok(Statement { db, stmt })
+    }
+
+    /// Bind a cell value to the 1-based parameter `i`.
+    pub fn bind_data(&self, i: c_int, data: &Data) -> Result<()> {
+        let rc = match data {
+            Data::Int(v) => self.bind_int64(i, *v),
+            Data::Float(v) => unsafe { sqlite3ext_bind_double(self.stmt, i, *v) },
+            Data::Bool(v) => self.bind_int64(i, i64::from(*v)),
+            Data::String(s) | Data::DateTimeIso(s) | Data::DurationIso(s) => self.bind_text(i, s)?,
+            Data::DateTime(dt) => self.bind_text(i, &crate::xl_datetime_text(dt))?,
+            Data::Error(e) => self.bind_text(i, &e.to_string())?,
+            Data::Empty => unsafe { sqlite3ext_bind_null(self.stmt, i) },
+        };
+        self.check_bind(rc)
+    }
+
+    /// Bind an SQL value (such as a constraint argument) to parameter `i`.
+    pub fn bind_value(&self, i: c_int, value: &*mut sqlite3_value) -> Result<()> {
+        let rc = match api::value_type(value) {
+            api::ValueType::Integer => self.bind_int64(i, api::value_int64(value)),
+            api::ValueType::Float => unsafe {
+                sqlite3ext_bind_double(self.stmt, i, api::value_double(value))
+            },
+            api::ValueType::Text => self.bind_text(i, api::value_text(value)?)?,
+            api::ValueType::Blob => {
+                let blob = api::value_blob(value);
+                let len = c_int::try_from(blob.len())
+                    .map_err(|_| Error::new_message("blob value is too long"))?;
+                unsafe {
+                    sqlite3ext_bind_blob(self.stmt, i, blob.as_ptr() as *const c_void, len, transient())
+                }
+            }
+            api::ValueType::Null => unsafe { sqlite3ext_bind_null(self.stmt, i) },
+        };
+        self.check_bind(rc)
+    }
+
+    fn check_bind(&self, rc: c_int) -> Result<()> {
+        if rc != SQLITE_OK {
+            return Err(Error::new_message(last_error(self.db)));
+        }
+        Ok(())
+    }
+
+    fn bind_int64(&self, i: c_int, v: i64) -> c_int {
+        unsafe { sqlite3ext_bind_int64(self.stmt, i, v) }
+    }
+
+    fn bind_text(&self, i: c_int, s: &str) -> Result<c_int> {
+        let len = c_int::try_from(s.len()).map_err(|_| Error::new_message("text value is too long"))?;
+        Ok(unsafe {
+            sqlite3ext_bind_text(self.stmt, i, s.as_ptr() as *const _, len, transient())
+        })
+    }
+
+    /// Step the statement, returning `true` while it produces rows.
+    pub fn step(&self) -> Result<bool> {
+        match unsafe { sqlite3ext_step(self.stmt) } {
+            SQLITE_ROW => Ok(true),
+            SQLITE_DONE => Ok(false),
+            _ => Err(Error::new_message(last_error(self.db))),
+        }
+    }
+
+    /// Value of the 0-based result column `i` of the current row.
+    pub fn column_value(&self, i: c_int) -> *mut sqlite3_value {
+        unsafe { sqlite3ext_column_value(self.stmt, i) }
+    }
+}
+
+impl Drop for Statement {
+    fn drop(&mut self) {
+        unsafe {
+            sqlite3ext_finalize(self.stmt);
+        }
+    }
+}
+
+/// Run a statement that returns no rows.
+pub fn execute(db: *mut sqlite3, sql: &str) -> Result<()> {
+    let stmt = Statement::prepare(db, sql)?;
+    while stmt.step()? {}
+    Ok(())
+}
+
+/// Replace the contents of `table` with `rows`, each holding `num_columns` cells.
+pub fn load(db: *mut sqlite3, table: &str, num_columns: usize, rows: &[Vec<Data>]) -> Result<()> {
+    execute(db, "SAVEPOINT xl0_cache")?;
+    let result = (|| {
+        execute(db, &format!("DELETE FROM {table}"))?;
+        let row_sql = format!("({})", vec!["?"; num_columns].join(", "));
+        let batch = (MAX_PARAMETERS / num_columns.max(1)).max(1);
+        for chunk in rows.chunks(batch) {
+            let sql = format!(
+                "INSERT INTO {table} VALUES {}",
+                vec![row_sql.as_str(); chunk.len()].join(", ")
+            );
+            let stmt = Statement::prepare(db, &sql)?;
+            for (r, row) in chunk.iter().enumerate() {
+                for c in 0..num_columns {
+                    let data = row.get(c).unwrap_or(&Data::Empty);
+                    stmt.bind_data((r * num_columns + c + 1) as c_int, data)?;
+                }
+            }
+            stmt.step()?;
+        }
+        Ok(())
+    })();
+    if result.is_err() {
+        execute(db, "ROLLBACK TO xl0_cache")?;
+    }
+    execute(db, "RELEASE xl0_cache")?;
+    result
+}
@@ -6,16 +6,19 @@ use sqlite_loadable::{
 };
 use std::{mem, os::raw::c_int};
 
-use calamine::{Data, Reader};
+use calamine::Data;
+use serde_json::Value;
 
 use crate::parser::column_idx_to_name;
-use crate::sheet_range::{parse_sheet_reference, SheetTarget};
+use crate::sheet_range::{parse_sheet_areas, parse_sheet_reference, SheetTarget};
+use crate::workbook::Workbook;
 
-static CREATE_SQL: &str = "CREATE TABLE x(column_name, row_number, value, workbook hidden, range hidden, sheet hidden)";
+static CREATE_SQL: &str = "CREATE TABLE x(column_name, row_number, value, area_index, workbook hidden, range hidden, sheet hidden)";
 enum Columns {
   ColumnName,
     RowNumber,
     Value,
+    AreaIndex,
     Workbook,
     Range,
     Sheet,
@@ -25,13 +28,84 @@ fn column(index: i32) -> Option<Columns> {
         0 => Some(Columns::ColumnName),
         1 => Some(Columns::RowNumber),
         2 => Some(Columns::Value),
-        3 => Some(Columns::Workbook),
-        4 => Some(Columns::Range),
-        5 => Some(Columns::Sheet),
+        3 => Some(Columns::AreaIndex),
+        4 => Some(Columns::Workbook),
+        5 => Some(Columns::Range),
+        6 => Some(Columns::Sheet),
         _ => None,
     }
 }
 
+/// Most cells `xl_extract()` returns as one JSON value; larger ranges are
+/// better read with `xl_cells()` or `xl_rows()`.
+const MAX_EXTRACT_CELLS: usize = 100_000;
+
+/// Resolve a parsed target to `(start_col, start_row, end_col, end_row)`,
+/// with unbounded sides ending at the last row or column with data.
+fn resolve_bounds(target: &SheetTarget, total_rows: usize, max_cols: usize) -> (usize, usize, usize, usize) {
+    match target {
+        SheetTarget::Range(r) => {
+            (r.start.0 as usize, r.start.1 as usize, r.end.0 as usize, r.end.1 as usize)
+        }
+        SheetTarget::OpenRange(r) => {
+            let sc = r.start.col.unwrap_or(0) as usize;
+            let sr = r.start.row.unwrap_or(0) as usize;
+            let ec = r.end.col.map(|c| c as usize).unwrap_or_else(|| max_cols.saturating_sub(1));
+            let er = r.end.row.map(|r| r as usize).unwrap_or_else(|| total_rows.saturating_sub(1));
+            (sc, sr, ec, er)
+        }
+        SheetTarget::Cell(c) => {
+            (c.location.0 as usize, c.location.1 as usize, c.location.0 as usize, c.location.1 as usize)
+        }
+    }
+}
+
+/// `xl_extract(workbook, reference)`: the typed value of a single cell like
+/// `Summary!B2`, or a JSON array of rows for a range of at most
+/// `MAX_EXTRACT_CELLS` cells. Sheets default to the first one.
+pub fn xl_extract(context: *mut sqlite3_context, values: &[*mut sqlite3_value]) -> Result<()> {
+    let data = api::value_blob(&values[0]).to_vec();
+    let reference = api::value_text(&values[1])?;
+    let mut workbook = Workbook::open(data)
+        .map_err(|e| crate::Error::new_message(format!("cannot open workbook: {e}")))?;
+    let parsed = parse_sheet_reference(reference)
+        .and_then(|parsed| parsed.check_limits(workbook.limits()).map(|_| parsed))
+        .map_err(|e| crate::Error::new_message(format!("invalid reference '{reference}': {e}")))?;
+    let sheet_name = match parsed.sheet {
+        Some(ref s) => s.clone(),
+        None => workbook
+            .sheet_names()
+            .first()
+            .cloned()
+            .ok_or_else(|| crate::Error::new_message("workbook has no sheets"))?,
+    };
+    let worksheet_range = workbook.worksheet_range(&sheet_name)
+        .map_err(|_| crate::Error::new_message(format!("sheet '{}' not found", sheet_name)))?;
+
+    let cell = |row: usize, col: usize| {
+        worksheet_range.get_value((row as u32, col as u32)).unwrap_or(&Data::Empty)
+    };
+    if let SheetTarget::Cell(c) = &parsed.target {
+        return crate::result_xl_data(context, cell(c.location.1 as usize, c.location.0 as usize));
+    }
+
+    let (total_rows, max_cols) = worksheet_range
+        .end()
+        .map_or((0, 0), |(row, col)| (row as usize + 1, col as usize + 1));
+    let (start_col, start_row, end_col, end_row) = resolve_bounds(&parsed.target, total_rows, max_cols);
+    let num_cells = (end_row + 1).saturating_sub(start_row) * (end_col + 1).saturating_sub(start_col);
+    if num_cells > MAX_EXTRACT_CELLS {
+        return Err(crate::Error::new_message(format!(
+            "range '{reference}' has {num_cells} cells, more than the limit of {MAX_EXTRACT_CELLS}; use xl_cells() instead"
+        )));
+    }
+    let rows: Vec<Value> = (start_row..=end_row)
+        .map(|row| Value::Array((start_col..=end_col).map(|col| crate::xl_data_json(cell(row, col))).collect()))
+        .collect();
+    api::result_json(context, Value::Array(rows))?;
+    Ok(())
+}
+
 #[repr(C)]
 pub struct CellsTable {
     /// must be first
@@ -110,7 +184,8 @@ pub struct CellsCursor {
     /// Base class. Must be first
     base: sqlite3_vtab_cursor,
     rowid: i64,
-    values: Option<Vec<(usize, usize, Data)>>,
+    /// (area index, row index, column index, value)
+    values: Option<Vec<(usize, usize, usize, Data)>>,
 }
 impl CellsCursor {
     fn new() -> CellsCursor {
@@ -134,55 +209,53 @@ impl VTabCursor for CellsCursor {
         let range_str = api::value_text(values.get(1).unwrap()).unwrap();
         let data = raw.to_vec();
         let mut workbook =
-            calamine::open_workbook_auto_from_rs(std::io::Cursor::new(data))
+            Workbook::open(data)
                 .map_err(|e| crate::Error::new_message(format!("cannot open workbook: {e}")))?;
-        let parsed = parse_sheet_reference(range_str)
+        let areas = parse_sheet_areas(range_str)
             .map_err(|e| crate::Error::new_message(format!("invalid range: {e}")))?;
 
-        // Use sheet from parsed reference, then explicit 3rd arg, then default to first sheet
-        let sheet_name = if let Some(ref s) = parsed.sheet {
-            s.clone()
-        } else if idx_num == 2 {
-            api::value_text(values.get(2).unwrap())?.to_owned()
-        } else {
-            workbook.sheet_names().first().unwrap().clone()
-        };
-
-        let worksheet_range = workbook.worksheet_range(&sheet_name)
-            .map_err(|_| crate::Error::new_message(format!("sheet '{}' not found", sheet_name)))?;
-
-        let all_rows: Vec<&[Data]> = worksheet_range.rows().collect();
-        let total_rows = all_rows.len();
-        let max_cols = all_rows.iter().map(|r| r.len()).max().unwrap_or(0);
-
-        // Resolve bounds from the parsed target
-        let (start_col, start_row, end_col, end_row) = match parsed.target {
-            SheetTarget::Range(r) => {
-                (r.start.0 as usize, r.start.1 as usize, r.end.0 as usize, r.end.1 as usize)
-            }
-            SheetTarget::OpenRange(r) => {
-                let sc = r.start.col.unwrap_or(0) as usize;
-                let sr = r.start.row.unwrap_or(0) as usize;
-                let ec = r.end.col.map(|c| c as usize).unwrap_or_else(|| max_cols.saturating_sub(1));
-                let er = r.end.row.map(|r| r as usize).unwrap_or_else(|| total_rows.saturating_sub(1));
-                (sc, sr, ec, er)
-            }
-            SheetTarget::Cell(c) => {
-                (c.location.0 as usize, c.location.1 as usize, c.location.0 as usize, c.location.1 as usize)
-            }
-        };
-
-        let mut values: Vec<(usize, usize, Data)> = Vec::new();
-        for row_idx in start_row..=end_row {
-            if let Some(row_data) = all_rows.get(row_idx) {
-                for col_idx in start_col..=end_col {
-                    if let Some(cell) = row_data.get(col_idx) {
-                        values.push((row_idx, col_idx, cell.to_owned()));
-                    }
+        let mut cells: Vec<(usize, usize, usize, Data)> = Vec::new();
+        for (area_idx, parsed) in areas.into_iter().enumerate() {
+            parsed
+                .check_limits(workbook.limits())
+                .map_err(|e| crate::Error::new_message(format!("invalid range: {e}")))?;
+
+            // Use sheet from parsed reference, then explicit 3rd arg, then default to first sheet
+            let sheet_name = if let Some(ref s) = parsed.sheet {
+                s.clone()
+            } else if idx_num == 2 {
+                api::value_text(values.get(2).unwrap())?.to_owned()
+            } else {
+                workbook
+                    .sheet_names()
+                    .first()
+                    .cloned()
+                    .ok_or_else(|| crate::Error::new_message("workbook has no sheets"))?
+            };
+
+            let worksheet_range = workbook.worksheet_range(&sheet_name)
+                .map_err(|_| crate::Error::new_message(format!("sheet '{}' not found", sheet_name)))?;
+
+            // calamine positions are (row, col); read them as absolute sheet
+            // positions, like xl_extract(), and only within the used range
+            let (Some((ws_start_row, ws_start_col)), Some((ws_end_row, ws_end_col))) =
+                (worksheet_range.start(), worksheet_range.end())
+            else {
+                continue;
+            };
+            let (start_col, start_row, end_col, end_row) =
+                resolve_bounds(&parsed.target, ws_end_row as usize + 1, ws_end_col as usize + 1);
+
+            for row_idx in start_row.max(ws_start_row as usize)..=end_row.min(ws_end_row as usize) {
+                for col_idx in start_col.max(ws_start_col as usize)..=end_col.min(ws_end_col as usize) {
+                    let cell = worksheet_range
+                        .get_value((row_idx as u32, col_idx as u32))
+                        .unwrap_or(&Data::Empty);
+                    cells.push((area_idx, row_idx, col_idx, cell.to_owned()));
                 }
             }
         }
-        self.values = Some(values);
+        self.values = Some(cells);
         self.rowid = 0;
         Ok(())
     }
@@ -209,13 +282,16 @@ impl VTabCursor for CellsCursor {
             .unwrap();
         match column(i) {
             Some(Columns::RowNumber) => {
-                api::result_int64(context, (v.0 + 1).try_into().unwrap());
+                api::result_int64(context, (v.1 + 1).try_into().unwrap());
             }
             Some(Columns::ColumnName) => {
-                api::result_text(context, column_idx_to_name(v.1.try_into().unwrap()))?;
+                api::result_text(context, column_idx_to_name(v.2.try_into().unwrap()))?;
             }
             Some(Columns::Value) => {
-                crate::result_xl_data(context, &v.2)?;
+                crate::result_xl_data(context, &v.3)?;
+            }
+            Some(Columns::AreaIndex) => {
+                api::result_int64(context, (v.0 + 1).try_into().unwrap());
             }
             Some(Columns::Workbook) => {
                 //context_result_int(0);
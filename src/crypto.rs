@@ -0,0 +1,417 @@
+//! Decrypt password-protected workbooks.
+//!
+//! Excel stores an encrypted `.xlsx` as a compound file holding an
+//! `EncryptionInfo` stream and the encrypted package. Only the agile
+//! encryption of ECMA-376 (Excel 2010 and later) is supported.
+
+use std::io::{Cursor, Read};
+
+use aes::cipher::{
+    block_padding::NoPadding, BlockCipher, BlockDecryptMut, KeyInit, KeyIvInit,
+};
+use base64::Engine;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use sha1::Sha1;
+use sha2::{Digest, Sha256, Sha384, Sha512};
+use sqlite_loadable::prelude::*;
+use sqlite_loadable::{api, Error};
+use thiserror::Error;
+
+/// Block keys that derive each key from the password hash.
+const VERIFIER_INPUT_BLOCK_KEY: [u8; 8] = [0xfe, 0xa7, 0xd2, 0x76, 0x3b, 0x4b, 0x9e, 0x79];
+const VERIFIER_VALUE_BLOCK_KEY: [u8; 8] = [0xd7, 0xaa, 0x0f, 0x6d, 0x30, 0x61, 0x34, 0x4e];
+const KEY_VALUE_BLOCK_KEY: [u8; 8] = [0x14, 0x6e, 0x0b, 0xe7, 0xab, 0xac, 0xd0, 0xd6];
+
+/// The encrypted package is decrypted in segments of this size.
+const SEGMENT_SIZE: usize = 4096;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum DecryptError {
+    #[error("not an encrypted workbook")]
+    NotEncrypted,
+    #[error("wrong password")]
+    WrongPassword,
+    #[error("unsupported encryption: {0}")]
+    Unsupported(String),
+    #[error("invalid encrypted workbook: {0}")]
+    Invalid(String),
+}
+
+fn invalid(e: impl std::fmt::Display) -> DecryptError {
+    DecryptError::Invalid(e.to_string())
+}
+
+#[derive(Clone, Copy)]
+enum HashAlgorithm {
+    Sha1,
+    Sha256,
+    Sha384,
+    Sha512,
+}
+
+impl HashAlgorithm {
+    fn parse(name: &str) -> Result<HashAlgorithm, DecryptError> {
+        match name {
+            "SHA1" | "SHA-1" => Ok(HashAlgorithm::Sha1),
+            "SHA256" => Ok(HashAlgorithm::Sha256),
+            "SHA384" => Ok(HashAlgorithm::Sha384),
+            "SHA512" => Ok(HashAlgorithm::Sha512),
+            other => Err(DecryptError::Unsupported(format!("hash algorithm {other}"))),
+        }
+    }
+
+    fn digest(self, parts: &[&[u8]]) -> Vec<u8> {
+        fn run<D: Digest>(parts: &[&[u8]]) -> Vec<u8> {
+            let mut hasher = D::new();
+            for part in parts {
+                hasher.update(part);
+            }
+            hasher.finalize().to_vec()
+        }
+        match self {
+            HashAlgorithm::Sha1 => run::<Sha1>(parts),
+            HashAlgorithm::Sha256 => run::<Sha256>(parts),
+            HashAlgorithm::Sha384 => run::<Sha384>(parts),
+            HashAlgorithm::Sha512 => run::<Sha512>(parts),
+        }
+    }
+}
+
+/// Cipher parameters shared by `<keyData>` and `<p:encryptedKey>`.
+struct CipherParams {
+    salt: Vec<u8>,
+    block_size: usize,
+    key_bytes: usize,
+    hash_size: usize,
+    hash: HashAlgorithm,
+}
+
+/// The password key encryptor, `<p:encryptedKey>`.
+struct PasswordKey {
+    params: CipherParams,
+    spin_count: u32,
+    verifier_hash_input: Vec<u8>,
+    verifier_hash_value: Vec<u8>,
+    key_value: Vec<u8>,
+}
+
+fn attribute(e: &BytesStart, name: &str) -> Result<String, DecryptError> {
+    let attr = e
+        .try_get_attribute(name)
+        .map_err(invalid)?
+        .ok_or_else(|| invalid(format!("missing attribute {name}")))?;
+    Ok(String::from_utf8_lossy(&attr.value).into_owned())
+}
+
+fn number(e: &BytesStart, name: &str) -> Result<usize, DecryptError> {
+    attribute(e, name)?.parse().map_err(|_| invalid(format!("invalid {name}")))
+}
+
+fn base64(e: &BytesStart, name: &str) -> Result<Vec<u8>, DecryptError> {
+    base64::engine::general_purpose::STANDARD.decode(attribute(e, name)?).map_err(invalid)
+}
+
+/// Excel never writes more than this many hash iterations; a larger
+/// `spinCount` would only stall the connection.
+const MAX_SPIN_COUNT: u32 = 10_000_000;
+
+fn spin_count(e: &BytesStart) -> Result<u32, DecryptError> {
+    let spin_count: u32 = attribute(e, "spinCount")?
+        .parse()
+        .map_err(|_| invalid("invalid spinCount"))?;
+    if spin_count > MAX_SPIN_COUNT {
+        return Err(invalid(format!(
+            "spinCount {spin_count} is above the limit of {MAX_SPIN_COUNT}"
+        )));
+    }
+    Ok(spin_count)
+}
+
+fn cipher_params(e: &BytesStart) -> Result<CipherParams, DecryptError> {
+    let cipher = attribute(e, "cipherAlgorithm")?;
+    let chaining = attribute(e, "cipherChaining")?;
+    if cipher != "AES" || chaining != "ChainingModeCBC" {
+        return Err(DecryptError::Unsupported(format!("{cipher} with {chaining}")));
+    }
+    Ok(CipherParams {
+        salt: base64(e, "saltValue")?,
+        block_size: number(e, "blockSize")?,
+        key_bytes: number(e, "keyBits")? / 8,
+        hash_size: number(e, "hashSize")?,
+        hash: HashAlgorithm::parse(&attribute(e, "hashAlgorithm")?)?,
+    })
+}
+
+/// Read the `<keyData>` and password `<p:encryptedKey>` of an agile
+/// `EncryptionInfo` stream.
+fn parse_encryption_info(info: &[u8]) -> Result<(CipherParams, PasswordKey), DecryptError> {
+    if info.len() < 8 {
+        return Err(invalid("EncryptionInfo is too short"));
+    }
+    let version = (u16::from_le_bytes([info[0], info[1]]), u16::from_le_bytes([info[2], info[3]]));
+    if version != (4, 4) {
+        return Err(DecryptError::Unsupported(format!(
+            "version {}.{}, only agile encryption can be decrypted",
+            version.0, version.1
+        )));
+    }
+    let mut reader = Reader::from_reader(&info[8..]);
+    let mut key_data = None;
+    let mut password_key = None;
+    loop {
+        match reader.read_event().map_err(invalid)? {
+            Event::Eof => break,
+            Event::Start(e) | Event::Empty(e) => match e.local_name().as_ref() {
+                b"keyData" => key_data = Some(cipher_params(&e)?),
+                b"encryptedKey" => {
+                    password_key = Some(PasswordKey {
+                        params: cipher_params(&e)?,
+                        spin_count: spin_count(&e)?,
+                        verifier_hash_input: base64(&e, "encryptedVerifierHashInput")?,
+                        verifier_hash_value: base64(&e, "encryptedVerifierHashValue")?,
+                        key_value: base64(&e, "encryptedKeyValue")?,
+                    })
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+    match (key_data, password_key) {
+        (Some(key_data), Some(password_key)) => Ok((key_data, password_key)),
+        (None, _) => Err(invalid("missing keyData")),
+        (_, None) => Err(DecryptError::Unsupported("no password key encryptor".to_owned())),
+    }
+}
+
+/// Truncate or pad (with `0x36`) a hash to the length a key or IV needs.
+fn fit(mut bytes: Vec<u8>, len: usize) -> Vec<u8> {
+    bytes.resize(len, 0x36);
+    bytes
+}
+
+fn aes_cbc_decrypt(key: &[u8], iv: &[u8], data: &[u8]) -> Result<Vec<u8>, DecryptError> {
+    fn run<C>(key: &[u8], iv: &[u8], buf: &mut [u8]) -> Result<(), DecryptError>
+    where
+        C: BlockDecryptMut + BlockCipher + KeyInit,
+    {
+        cbc::Decryptor::<C>::new_from_slices(key, iv)
+            .map_err(invalid)?
+            .decrypt_padded_mut::<NoPadding>(buf)
+            .map_err(|_| invalid("encrypted data is not a whole number of blocks"))?;
+        Ok(())
+    }
+    let mut buf = data.to_vec();
+    match key.len() {
+        16 => run::<aes::Aes128>(key, iv, &mut buf)?,
+        24 => run::<aes::Aes192>(key, iv, &mut buf)?,
+        32 => run::<aes::Aes256>(key, iv, &mut buf)?,
+        n => return Err(DecryptError::Unsupported(format!("{}-bit keys", n * 8))),
+    }
+    Ok(buf)
+}
+
+impl PasswordKey {
+    /// Derive the key for `block_key` from the password hash.
+    fn derive(&self, password_hash: &[u8], block_key: &[u8]) -> Vec<u8> {
+        let hash = self.params.hash.digest(&[password_hash, block_key]);
+        fit(hash, self.params.key_bytes)
+    }
+
+    /// Hash the salted password, then re-hash it `spin_count` times.
+    fn password_hash(&self, password: &str) -> Vec<u8> {
+        let password: Vec<u8> = password.encode_utf16().flat_map(u16::to_le_bytes).collect();
+        let hash = self.params.hash;
+        let mut h = hash.digest(&[&self.params.salt, &password]);
+        for i in 0..self.spin_count {
+            h = hash.digest(&[&i.to_le_bytes(), &h]);
+        }
+        h
+    }
+
+    fn decrypt(&self, password_hash: &[u8], block_key: &[u8], data: &[u8]) -> Result<Vec<u8>, DecryptError> {
+        let iv = fit(self.params.salt.clone(), self.params.block_size);
+        aes_cbc_decrypt(&self.derive(password_hash, block_key), &iv, data)
+    }
+
+    /// The key that encrypts the package, once the password is verified.
+    fn secret_key(&self, password: &str) -> Result<Vec<u8>, DecryptError> {
+        let h = self.password_hash(password);
+        let input = self.decrypt(&h, &VERIFIER_INPUT_BLOCK_KEY, &self.verifier_hash_input)?;
+        let value = self.decrypt(&h, &VERIFIER_VALUE_BLOCK_KEY, &self.verifier_hash_value)?;
+        let params = &self.params;
+        let expected = params.hash.digest(&[&input[..params.salt.len().min(input.len())]]);
+        if value.get(..params.hash_size) != expected.get(..params.hash_size) {
+            return Err(DecryptError::WrongPassword);
+        }
+        let key = self.decrypt(&h, &KEY_VALUE_BLOCK_KEY, &self.key_value)?;
+        Ok(key[..params.key_bytes.min(key.len())].to_vec())
+    }
+}
+
+fn read_stream(file: &mut cfb::CompoundFile<Cursor<&[u8]>>, name: &str) -> Result<Vec<u8>, DecryptError> {
+    let mut stream = file.open_stream(name).map_err(|_| DecryptError::NotEncrypted)?;
+    let mut data = Vec::new();
+    stream.read_to_end(&mut data).map_err(invalid)?;
+    Ok(data)
+}
+
+/// Decrypt an encrypted workbook into the plain `.xlsx` package.
+pub fn decrypt(data: &[u8], password: &str) -> Result<Vec<u8>, DecryptError> {
+    let mut file = cfb::CompoundFile::open(Cursor::new(data)).map_err(|_| DecryptError::NotEncrypted)?;
+    let info = read_stream(&mut file, "/EncryptionInfo")?;
+    let package = read_stream(&mut file, "/EncryptedPackage")?;
+    let (key_data, password_key) = parse_encryption_info(&info)?;
+    let key = password_key.secret_key(password)?;
+
+    if package.len() < 8 {
+        return Err(invalid("EncryptedPackage is too short"));
+    }
+    let size = u64::from_le_bytes(package[..8].try_into().unwrap()) as usize;
+    let mut plain = Vec::with_capacity(package.len() - 8);
+    for (i, segment) in package[8..].chunks(SEGMENT_SIZE).enumerate() {
+        let iv = key_data.hash.digest(&[&key_data.salt, &(i as u32).to_le_bytes()]);
+        plain.extend(aes_cbc_decrypt(&key, &fit(iv, key_data.block_size), segment)?);
+    }
+    if plain.len() < size {
+        return Err(invalid("EncryptedPackage is truncated"));
+    }
+    plain.truncate(size);
+    Ok(plain)
+}
+
+/// `xl_decrypt(workbook, password)`: the decrypted workbook, which can be
+/// passed to any other function.
+pub fn xl_decrypt(
+    context: *mut sqlite3_context,
+    values: &[*mut sqlite3_value],
+) -> sqlite_loadable::Result<()> {
+    let password = api::value_text(&values[1])?;
+    let plain = decrypt(api::value_blob(&values[0]), password)
+        .map_err(|e| Error::new_message(format!("cannot decrypt workbook: {e}")))?;
+    api::result_blob(context, &plain);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aes::cipher::BlockEncryptMut;
+    use std::io::Write;
+
+    fn aes256_cbc_encrypt(key: &[u8], iv: &[u8], data: &[u8]) -> Vec<u8> {
+        let mut buf = data.to_vec();
+        buf.resize(data.len().div_ceil(16) * 16, 0);
+        let len = buf.len();
+        cbc::Encryptor::<aes::Aes256>::new_from_slices(key, iv)
+            .unwrap()
+            .encrypt_padded_mut::<NoPadding>(&mut buf, len)
+            .unwrap();
+        buf
+    }
+
+    /// Encrypt a workbook the way Excel does, with AES-256 and SHA-512.
+    fn encrypt(data: &[u8], password: &str, spin_count: u32) -> Vec<u8> {
+        let key_salt: Vec<u8> = (0..16).collect();
+        let password_salt: Vec<u8> = (16..32).collect();
+        let secret: Vec<u8> = (100..132).collect();
+        let verifier_input: Vec<u8> = (200..216).collect();
+        let hash = HashAlgorithm::Sha512;
+
+        let password_key = PasswordKey {
+            params: CipherParams {
+                salt: password_salt.clone(),
+                block_size: 16,
+                key_bytes: 32,
+                hash_size: 64,
+                hash,
+            },
+            spin_count,
+            verifier_hash_input: Vec::new(),
+            verifier_hash_value: Vec::new(),
+            key_value: Vec::new(),
+        };
+        let h = password_key.password_hash(password);
+        let encrypt_with = |block_key: &[u8], data: &[u8]| {
+            aes256_cbc_encrypt(&password_key.derive(&h, block_key), &password_salt, data)
+        };
+        let b64 = |bytes: &[u8]| base64::engine::general_purpose::STANDARD.encode(bytes);
+        let params = |salt: &[u8]| {
+            format!(
+                r#"saltSize="16" blockSize="16" keyBits="256" hashSize="64" cipherAlgorithm="AES" cipherChaining="ChainingModeCBC" hashAlgorithm="SHA512" saltValue="{}""#,
+                b64(salt)
+            )
+        };
+        let xml = format!(
+            r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<encryption xmlns="http://schemas.microsoft.com/office/2006/encryption" xmlns:p="http://schemas.microsoft.com/office/2006/keyEncryptor/password"><keyData {}/><keyEncryptors><keyEncryptor uri="http://schemas.microsoft.com/office/2006/keyEncryptor/password"><p:encryptedKey spinCount="{spin_count}" {} encryptedVerifierHashInput="{}" encryptedVerifierHashValue="{}" encryptedKeyValue="{}"/></keyEncryptor></keyEncryptors></encryption>"#,
+            params(&key_salt),
+            params(&password_salt),
+            b64(&encrypt_with(&VERIFIER_INPUT_BLOCK_KEY, &verifier_input)),
+            b64(&encrypt_with(&VERIFIER_VALUE_BLOCK_KEY, &hash.digest(&[&verifier_input]))),
+            b64(&encrypt_with(&KEY_VALUE_BLOCK_KEY, &secret)),
+        );
+        let mut info = vec![4, 0, 4, 0, 0x40, 0, 0, 0];
+        info.extend(xml.as_bytes());
+
+        let mut package = (data.len() as u64).to_le_bytes().to_vec();
+        for (i, segment) in data.chunks(SEGMENT_SIZE).enumerate() {
+            let iv = hash.digest(&[&key_salt, &(i as u32).to_le_bytes()]);
+            package.extend(aes256_cbc_encrypt(&secret, &iv[..16], segment));
+        }
+
+        let mut file = cfb::CompoundFile::create(Cursor::new(Vec::new())).unwrap();
+        file.create_stream("/EncryptionInfo").unwrap().write_all(&info).unwrap();
+        file.create_stream("/EncryptedPackage").unwrap().write_all(&package).unwrap();
+        file.flush().unwrap();
+        file.into_inner().into_inner()
+    }
+
+    #[test]
+    fn round_trip() {
+        let workbook = include_bytes!("../tests/students.xlsx");
+        let encrypted = encrypt(workbook, "secret", 100);
+        assert!(crate::workbook::is_encrypted(&encrypted));
+        assert_eq!(decrypt(&encrypted, "secret").unwrap(), workbook);
+        assert_eq!(decrypt(&encrypted, "Secret"), Err(DecryptError::WrongPassword));
+    }
+
+    #[test]
+    fn fixture() {
+        let encrypted = include_bytes!("../tests/encrypted.xlsx");
+        let plain = decrypt(encrypted, "secret").unwrap();
+        let workbook = crate::workbook::Workbook::open(plain).unwrap();
+        assert_eq!(workbook.sheet_names()[0], "students");
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(decrypt(b"not encrypted", "x"), Err(DecryptError::NotEncrypted));
+        assert_eq!(
+            decrypt(include_bytes!("../tests/students.xlsx"), "x"),
+            Err(DecryptError::NotEncrypted)
+        );
+        let mut info = vec![3, 0, 2, 0, 0, 0, 0, 0];
+        info.extend(b"<encryption/>");
+        assert!(matches!(parse_encryption_info(&info), Err(DecryptError::Unsupported(_))));
+    }
+
+    #[test]
+    fn spin_count_limit() {
+        let params = r#"blockSize="16" keyBits="256" hashSize="64" cipherAlgorithm="AES" cipherChaining="ChainingModeCBC" hashAlgorithm="SHA512" saltValue="AAAA""#;
+        let mut info = vec![4, 0, 4, 0, 0x40, 0, 0, 0];
+        info.extend(
+            format!(
+                r#"<encryption><keyData {params}/><p:encryptedKey spinCount="4294967295" {params} encryptedVerifierHashInput="" encryptedVerifierHashValue="" encryptedKeyValue=""/></encryption>"#
+            )
+            .as_bytes(),
+        );
+        assert_eq!(
+            parse_encryption_info(&info).err(),
+            Some(DecryptError::Invalid(
+                "spinCount 4294967295 is above the limit of 10000000".to_owned()
+            ))
+        );
+    }
+}
@@ -0,0 +1,199 @@
+use calamine::{Data, Range};
+use serde_json::json;
+use sqlite_loadable::prelude::*;
+use sqlite_loadable::{api, Error, Result};
+
+use crate::parser::column_idx_to_name;
+use crate::sheet_range::quote_sheet_name;
+use crate::workbook::Workbook;
+
+/// A table-like region found inside a worksheet.
+#[derive(Debug, PartialEq)]
+pub struct DetectedTable {
+    /// 0-based (column, row) of the top-left cell
+    pub start: (u32, u32),
+    /// 0-based (column, row) of the bottom-right cell
+    pub end: (u32, u32),
+    /// Whether the first row of the region looks like a header row
+    pub has_header: bool,
+}
+
+impl DetectedTable {
+    /// The region as an A1 range like `A13:W42`.
+    pub fn a1(&self) -> String {
+        format!(
+            "{}{}:{}{}",
+            column_idx_to_name(self.start.0),
+            self.start.1 + 1,
+            column_idx_to_name(self.end.0),
+            self.end.1 + 1
+        )
+    }
+}
+
+/// Find the first dense rectangular region in a worksheet.
+///
+/// Rows are scanned top to bottom, skipping title blocks: a region starts at
+/// the first row with at least two values that fill at least half of its
+/// span, and continues down until a row with no values inside that span.
+/// The region's first row is considered a header when all of its values are
+/// strings.
+pub fn detect_table(worksheet: &Range<Data>) -> Option<DetectedTable> {
+    let (row0, col0) = worksheet.start()?;
+    let rows: Vec<&[Data]> = worksheet.rows().collect();
+
+    let mut r = 0;
+    while r < rows.len() {
+        let filled: Vec<usize> = rows[r]
+            .iter()
+            .enumerate()
+            .filter(|(_, d)| !matches!(d, Data::Empty))
+            .map(|(i, _)| i)
+            .collect();
+        let (first, last) = match (filled.first(), filled.last()) {
+            (Some(first), Some(last)) => (*first, *last),
+            _ => {
+                r += 1;
+                continue;
+            }
+        };
+        if filled.len() < 2 || filled.len() * 2 < last - first + 1 {
+            r += 1;
+            continue;
+        }
+
+        let mut end = r;
+        while end + 1 < rows.len()
+            && rows[end + 1][first..=last]
+                .iter()
+                .any(|d| !matches!(d, Data::Empty))
+        {
+            end += 1;
+        }
+        if end == r {
+            r += 1;
+            continue;
+        }
+
+        let has_header = rows[r][first..=last]
+            .iter()
+            .all(|d| matches!(d, Data::String(_) | Data::Empty));
+        return Some(DetectedTable {
+            start: (col0 + first as u32, row0 + r as u32),
+            end: (col0 + last as u32, row0 + end as u32),
+            has_header,
+        });
+    }
+    None
+}
+
+pub fn xl_detect_table(context: *mut sqlite3_context, values: &[*mut sqlite3_value]) -> Result<()> {
+    let data = api::value_blob(&values[0]).to_vec();
+    let mut workbook = Workbook::open(data)
+        .map_err(|e| Error::new_message(format!("cannot open workbook: {e}")))?;
+    let sheet = match values.get(1) {
+        Some(value) => api::value_text(value)?.to_owned(),
+        None => workbook
+            .sheet_names()
+            .first()
+            .cloned()
+            .ok_or_else(|| Error::new_message("workbook has no sheets"))?,
+    };
+    let worksheet = workbook
+        .worksheet_range(&sheet)
+        .map_err(|_| Error::new_message(format!("sheet '{sheet}' not found")))?;
+
+    match detect_table(&worksheet) {
+        Some(table) => {
+            let data_rows = table.end.1 - table.start.1 + 1 - u32::from(table.has_header);
+            api::result_json(
+                context,
+                json!({
+                    "sheet": sheet,
+                    "range": format!("{}!{}", quote_sheet_name(&sheet), table.a1()),
+                    "header_row": if table.has_header { Some(table.start.1 + 1) } else { None },
+                    "rows": data_rows,
+                    "columns": table.end.0 - table.start.0 + 1,
+                }),
+            )?;
+        }
+        None => api::result_null(context),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sheet(cells: &[((u32, u32), Data)]) -> Range<Data> {
+        let cells = cells
+            .iter()
+            .map(|(pos, value)| calamine::Cell::new(*pos, value.clone()))
+            .collect();
+        Range::from_sparse(cells)
+    }
+
+    fn s(v: &str) -> Data {
+        Data::String(v.to_owned())
+    }
+
+    #[test]
+    fn skips_title_block() {
+        // title in A1, header in B4:D4, two data rows, a note below a blank row
+        let ws = sheet(&[
+            ((0, 0), s("Report")),
+            ((3, 1), s("region")),
+            ((3, 2), s("q1")),
+            ((3, 3), s("q2")),
+            ((4, 1), s("North")),
+            ((4, 2), Data::Float(1.0)),
+            ((4, 3), Data::Float(2.0)),
+            ((5, 1), s("South")),
+            ((5, 2), Data::Float(3.0)),
+            ((5, 3), Data::Float(4.0)),
+            ((7, 1), s("Note")),
+        ]);
+        let table = detect_table(&ws).unwrap();
+        assert_eq!(
+            table,
+            DetectedTable {
+                start: (1, 3),
+                end: (3, 5),
+                has_header: true,
+            }
+        );
+        assert_eq!(table.a1(), "B4:D6");
+    }
+
+    #[test]
+    fn numeric_first_row_is_not_a_header() {
+        let ws = sheet(&[
+            ((0, 0), Data::Float(1.0)),
+            ((0, 1), Data::Float(2.0)),
+            ((1, 0), Data::Float(3.0)),
+            ((1, 1), Data::Float(4.0)),
+        ]);
+        let table = detect_table(&ws).unwrap();
+        assert_eq!(table.a1(), "A1:B2");
+        assert!(!table.has_header);
+    }
+
+    #[test]
+    fn offset_used_area() {
+        // calamine ranges start at the first used cell, positions stay absolute
+        let ws = sheet(&[
+            ((9, 4), s("a")),
+            ((9, 5), s("b")),
+            ((10, 4), Data::Float(1.0)),
+            ((10, 5), Data::Float(2.0)),
+        ]);
+        assert_eq!(detect_table(&ws).unwrap().a1(), "E10:F11");
+    }
+
+    #[test]
+    fn no_table() {
+        assert_eq!(detect_table(&sheet(&[((0, 0), s("only a title"))])), None);
+        assert_eq!(detect_table(&Range::empty()), None);
+    }
+}
@@ -0,0 +1,443 @@
+//! Build `.xlsx` workbooks from query results.
+
+use calamine::Data;
+use rust_xlsxwriter::{ExcelDateTime, Format, Workbook, Worksheet, XlsxError};
+use serde_json::Value;
+use sqlite_loadable::ext::{sqlite3ext_aggregate_context, sqlite3ext_create_function_v2};
+use sqlite_loadable::prelude::*;
+use sqlite_loadable::{api, Error, Result};
+use std::ffi::CString;
+use std::os::raw::c_int;
+use std::{mem, ptr};
+
+const SQLITE_OK: c_int = 0;
+const SQLITE_ERROR: c_int = 1;
+const SQLITE_NOMEM: c_int = 7;
+
+/// Largest number of columns in an `.xlsx` sheet.
+const MAX_COLUMNS: usize = 16_384;
+
+fn xlsx_error(e: XlsxError) -> Error {
+    Error::new_message(format!("cannot build workbook: {e}"))
+}
+
+/// A 0-based column index for rust_xlsxwriter, within the sheet's limit.
+fn column(col: usize) -> Result<u16> {
+    u16::try_from(col)
+        .ok()
+        .filter(|_| col < MAX_COLUMNS)
+        .ok_or_else(|| Error::new_message(format!("too many columns: a sheet has at most {MAX_COLUMNS}")))
+}
+
+/// Whether text looks like an ISO 8601 date (`2024-01-15`) or datetime
+/// (`2024-01-15 09:30:00`, `2024-01-15T09:30:00`), as returned for date cells.
+pub fn is_iso_date(s: &str) -> bool {
+    let b = s.as_bytes();
+    let digits = |range: std::ops::Range<usize>| b[range].iter().all(u8::is_ascii_digit);
+    let date = b.len() >= 10 && digits(0..4) && b[4] == b'-' && digits(5..7) && b[7] == b'-' && digits(8..10);
+    let time = b.len() == 10
+        || (b.len() >= 16 && matches!(b[10], b' ' | b'T') && digits(11..13) && b[13] == b':' && digits(14..16));
+    date && time && ExcelDateTime::parse_from_str(s).is_ok()
+}
+
+/// Convert a JSON value from a query row into a cell value, the reverse of
+/// how cells are returned to SQL: ISO date text becomes a date cell.
+pub fn json_to_data(value: &Value) -> Data {
+    match value {
+        Value::Null => Data::Empty,
+        Value::Bool(b) => Data::Bool(*b),
+        Value::Number(n) => match n.as_i64() {
+            Some(i) => Data::Int(i),
+            None => Data::Float(n.as_f64().unwrap_or(f64::NAN)),
+        },
+        Value::String(s) if is_iso_date(s) => Data::DateTimeIso(s.clone()),
+        Value::String(s) => Data::String(s.clone()),
+        other => Data::String(other.to_string()),
+    }
+}
+
+/// Number format for a date cell: with a time part only when there is one.
+fn date_format(has_time: bool) -> Format {
+    Format::new().set_num_format(if has_time { "yyyy-mm-dd hh:mm:ss" } else { "yyyy-mm-dd" })
+}
+
+/// Write a cell value into a worksheet. 0-based row and column.
+pub fn write_data(worksheet: &mut Worksheet, row: u32, col: u16, data: &Data) -> Result<()> {
+    match data {
+        Data::Empty => return Ok(()),
+        Data::Int(v) => worksheet.write_number(row, col, *v as f64),
+        Data::Float(v) => worksheet.write_number(row, col, *v),
+        Data::Bool(v) => worksheet.write_boolean(row, col, *v),
+        Data::String(s) | Data::DurationIso(s) => worksheet.write_string(row, col, s),
+        Data::DateTimeIso(s) => match ExcelDateTime::parse_from_str(s) {
+            Ok(dt) => worksheet.write_datetime_with_format(row, col, dt, &date_format(s.len() > 10)),
+            Err(_) => worksheet.write_string(row, col, s),
+        },
+        Data::DateTime(dt) => {
+            let serial = dt.as_f64();
+            worksheet.write_number_with_format(row, col, serial, &date_format(serial.fract() != 0.0))
+        }
+        Data::Error(e) => worksheet.write_string(row, col, e.to_string()),
+    }
+    .map_err(xlsx_error)?;
+    Ok(())
+}
+
+/// Write query rows into a worksheet.
+///
+/// `rows` is a JSON array of objects, as built by `json_group_array(json_object(...))`:
+/// the keys become a header row, in the order they first appear. Rows may
+/// also be arrays, which are written as-is without a header. Returns the
+/// header names.
+pub fn write_rows(
+    worksheet: &mut Worksheet,
+    rows: &Value,
+    header_format: Option<&Format>,
+) -> Result<Vec<String>> {
+    let rows = rows
+        .as_array()
+        .ok_or_else(|| Error::new_message("rows must be a JSON array of objects or arrays"))?;
+
+    let mut header: Vec<String> = Vec::new();
+    for row in rows {
+        if let Value::Object(object) = row {
+            for key in object.keys() {
+                if !header.contains(key) {
+                    header.push(key.clone());
+                }
+            }
+        }
+    }
+    for (col, name) in header.iter().enumerate() {
+        match header_format {
+            Some(format) => worksheet.write_string_with_format(0, column(col)?, name, format),
+            None => worksheet.write_string(0, column(col)?, name),
+        }
+        .map_err(xlsx_error)?;
+    }
+
+    let first_row = u32::from(!header.is_empty());
+    for (i, row) in rows.iter().enumerate() {
+        let r = first_row + i as u32;
+        match row {
+            Value::Object(object) => {
+                for (col, name) in header.iter().enumerate() {
+                    if let Some(value) = object.get(name) {
+                        write_data(worksheet, r, column(col)?, &json_to_data(value))?;
+                    }
+                }
+            }
+            Value::Array(values) => {
+                for (col, value) in values.iter().enumerate() {
+                    write_data(worksheet, r, column(col)?, &json_to_data(value))?;
+                }
+            }
+            _ => return Err(Error::new_message("each row must be a JSON object or array")),
+        }
+    }
+    Ok(header)
+}
+
+/// Build a single-sheet workbook from query rows.
+pub fn export(sheet: &str, rows: &Value) -> Result<Vec<u8>> {
+    let mut workbook = Workbook::new();
+    let worksheet = workbook.add_worksheet();
+    worksheet.set_name(sheet).map_err(xlsx_error)?;
+    write_rows(worksheet, rows, None)?;
+    workbook.save_to_buffer().map_err(xlsx_error)
+}
+
+/// Build a workbook from a JSON array of sheets, as returned by `xl_sheet()`.
+///
+/// Each sheet is an object with a `name`, its `rows` (see [`write_rows`]) and
+/// optional formatting: `bold_header`, `freeze_header`, `autofit`, and
+/// `column_widths` as an array of widths or an object keyed by header name.
+pub fn build_workbook(sheets: &Value) -> Result<Vec<u8>> {
+    let sheets = sheets
+        .as_array()
+        .ok_or_else(|| Error::new_message("sheets must be a JSON array"))?;
+    if sheets.is_empty() {
+        return Err(Error::new_message("a workbook needs at least one sheet"));
+    }
+    let mut workbook = Workbook::new();
+    for sheet in sheets {
+        // sheets passed through json_array() as text are parsed again
+        let sheet = match sheet {
+            Value::String(s) => serde_json::from_str(s)
+                .map_err(|e| Error::new_message(format!("invalid sheet: {e}")))?,
+            other => other.clone(),
+        };
+        let name = sheet
+            .get("name")
+            .and_then(Value::as_str)
+            .ok_or_else(|| Error::new_message("each sheet needs a name"))?;
+        let flag = |key: &str| sheet.get(key).and_then(Value::as_bool).unwrap_or(false);
+
+        let worksheet = workbook.add_worksheet();
+        worksheet.set_name(name).map_err(xlsx_error)?;
+        let bold = Format::new().set_bold();
+        let header = write_rows(
+            worksheet,
+            sheet.get("rows").unwrap_or(&Value::Array(Vec::new())),
+            flag("bold_header").then_some(&bold),
+        )?;
+        if flag("autofit") {
+            worksheet.autofit();
+        }
+        match sheet.get("column_widths") {
+            Some(Value::Array(widths)) => {
+                for (col, width) in widths.iter().enumerate() {
+                    if let Some(width) = width.as_f64() {
+                        worksheet.set_column_width(column(col)?, width).map_err(xlsx_error)?;
+                    }
+                }
+            }
+            Some(Value::Object(widths)) => {
+                for (name, width) in widths {
+                    let col = header.iter().position(|h| h == name).ok_or_else(|| {
+                        Error::new_message(format!("column_widths: no column named '{name}'"))
+                    })?;
+                    if let Some(width) = width.as_f64() {
+                        worksheet.set_column_width(column(col)?, width).map_err(xlsx_error)?;
+                    }
+                }
+            }
+            _ => {}
+        }
+        if flag("freeze_header") && !header.is_empty() {
+            worksheet.set_freeze_panes(1, 0).map_err(xlsx_error)?;
+        }
+    }
+    workbook.save_to_buffer().map_err(xlsx_error)
+}
+
+fn parse_json(value: &*mut sqlite3_value, what: &str) -> Result<Value> {
+    serde_json::from_str(api::value_text(value)?)
+        .map_err(|e| Error::new_message(format!("{what} must be JSON: {e}")))
+}
+
+/// Rows gathered by one group of the `xl_export()` aggregate.
+#[derive(Default)]
+struct ExportState {
+    sheet: Option<String>,
+    rows: Vec<Value>,
+}
+
+impl ExportState {
+    fn step(&mut self, values: &[*mut sqlite3_value]) -> Result<()> {
+        if self.sheet.is_none() {
+            self.sheet = Some(api::value_text(&values[0])?.to_owned());
+        }
+        self.rows.push(parse_json(&values[1], "row")?);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Vec<u8>> {
+        export(self.sheet.as_deref().unwrap_or_default(), &Value::Array(self.rows))
+    }
+}
+
+fn result_error(context: *mut sqlite3_context, error: Error) {
+    if api::result_error(context, &error.result_error_message()).is_err() {
+        api::result_error_code(context, SQLITE_ERROR);
+    }
+}
+
+/// The aggregate context holds a pointer to the group's boxed state, which
+/// `xl_export_final` takes back and drops.
+unsafe fn export_state(context: *mut sqlite3_context, create: bool) -> *mut *mut ExportState {
+    let size = if create { mem::size_of::<*mut ExportState>() as c_int } else { 0 };
+    sqlite3ext_aggregate_context(context, size).cast()
+}
+
+unsafe extern "C" fn xl_export_step(
+    context: *mut sqlite3_context,
+    argc: c_int,
+    argv: *mut *mut sqlite3_value,
+) {
+    let slot = export_state(context, true);
+    if slot.is_null() {
+        api::result_error_code(context, SQLITE_NOMEM);
+        return;
+    }
+    if (*slot).is_null() {
+        *slot = Box::into_raw(Box::default());
+    }
+    let values = std::slice::from_raw_parts(argv, argc as usize);
+    if let Err(e) = (**slot).step(values) {
+        result_error(context, e);
+    }
+}
+
+unsafe extern "C" fn xl_export_final(context: *mut sqlite3_context) {
+    let slot = export_state(context, false);
+    if slot.is_null() || (*slot).is_null() {
+        // no rows: there is no sheet to build
+        api::result_null(context);
+        return;
+    }
+    let state = Box::from_raw(mem::replace(&mut *slot, ptr::null_mut()));
+    match state.finish() {
+        Ok(workbook) => api::result_blob(context, &workbook),
+        Err(e) => result_error(context, e),
+    }
+}
+
+/// Register the `xl_export(sheet_name, row)` aggregate: a workbook with one
+/// sheet holding every row of the group, or NULL when there are no rows.
+pub fn define_xl_export(db: *mut sqlite3) -> Result<()> {
+    let name = CString::new("xl_export").expect("function name has no NUL byte");
+    let rc = unsafe {
+        sqlite3ext_create_function_v2(
+            db,
+            name.as_ptr(),
+            2,
+            FunctionFlags::UTF8.bits(),
+            ptr::null_mut(),
+            None,
+            Some(xl_export_step),
+            Some(xl_export_final),
+            None,
+        )
+    };
+    if rc != SQLITE_OK {
+        return Err(Error::new_message(format!("cannot define xl_export (error code {rc})")));
+    }
+    Ok(())
+}
+
+/// `xl_sheet(name, rows [, options])`: describe one sheet for `xl_workbook()`.
+pub fn xl_sheet(context: *mut sqlite3_context, values: &[*mut sqlite3_value]) -> Result<()> {
+    let mut sheet = match values.get(2) {
+        Some(options) => match parse_json(options, "options")? {
+            Value::Object(options) => options,
+            _ => return Err(Error::new_message("options must be a JSON object")),
+        },
+        None => serde_json::Map::new(),
+    };
+    sheet.insert("name".to_owned(), Value::String(api::value_text(&values[0])?.to_owned()));
+    sheet.insert("rows".to_owned(), parse_json(&values[1], "rows")?);
+    api::result_json(context, Value::Object(sheet))?;
+    Ok(())
+}
+
+/// `xl_workbook(sheets)`: build a workbook blob from `xl_sheet()` values.
+pub fn xl_workbook(context: *mut sqlite3_context, values: &[*mut sqlite3_value]) -> Result<()> {
+    let sheets = parse_json(&values[0], "sheets")?;
+    api::result_blob(context, &build_workbook(&sheets)?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use calamine::Reader;
+    use serde_json::json;
+
+    fn read(workbook: Vec<u8>, sheet: &str) -> Vec<Vec<Data>> {
+        let mut workbook =
+            calamine::open_workbook_auto_from_rs(std::io::Cursor::new(workbook)).unwrap();
+        let range = workbook.worksheet_range(sheet).unwrap();
+        range.rows().map(|r| r.to_vec()).collect()
+    }
+
+    #[test]
+    fn iso_dates() {
+        assert!(is_iso_date("2024-01-15"));
+        assert!(is_iso_date("2024-01-15 09:30:00"));
+        assert!(is_iso_date("2024-01-15T09:30"));
+        assert!(!is_iso_date("2024"));
+        assert!(!is_iso_date("2024-13-45"));
+        assert!(!is_iso_date("2024-01-15 and more"));
+    }
+
+    #[test]
+    fn round_trip() {
+        let rows = json!([
+            {"region": "North", "amount": 10.5, "active": true, "since": "2024-01-15"},
+            {"region": "South", "amount": 20, "active": false, "since": "2024-02-01 09:30:00", "note": "late"},
+        ]);
+        let rows = read(export("Sales", &rows).unwrap(), "Sales");
+        let header: Vec<Data> = ["region", "amount", "active", "since", "note"]
+            .iter()
+            .map(|s| Data::String(s.to_string()))
+            .collect();
+        assert_eq!(rows[0], header);
+        assert_eq!(rows[1][0], Data::String("North".into()));
+        assert_eq!(rows[1][1], Data::Float(10.5));
+        assert_eq!(rows[1][2], Data::Bool(true));
+        assert_eq!(rows[2][1], Data::Float(20.0));
+        assert_eq!(rows[1][4], Data::Empty);
+        match (&rows[1][3], &rows[2][3]) {
+            (Data::DateTime(date), Data::DateTime(datetime)) => {
+                assert_eq!(crate::xl_datetime_text(date), "2024-01-15");
+                assert_eq!(crate::xl_datetime_text(datetime), "2024-02-01 09:30:00");
+            }
+            other => panic!("expected dates, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn array_rows() {
+        let rows = read(export("Sheet1", &json!([[1, "a"], [2, null]])).unwrap(), "Sheet1");
+        assert_eq!(rows[0], vec![Data::Float(1.0), Data::String("a".into())]);
+        assert_eq!(rows[1][0], Data::Float(2.0));
+    }
+
+    fn part(workbook: &[u8], name: &str) -> String {
+        use std::io::Read;
+        let mut archive = zip::ZipArchive::new(std::io::Cursor::new(workbook)).unwrap();
+        let mut xml = String::new();
+        archive.by_name(name).unwrap().read_to_string(&mut xml).unwrap();
+        xml
+    }
+
+    #[test]
+    fn multiple_sheets() {
+        let sheets = json!([
+            {
+                "name": "Summary",
+                "rows": [{"total": 30}],
+                "bold_header": true,
+                "freeze_header": true,
+                "column_widths": {"total": 20},
+            },
+            // as passed through json_array() without a JSON subtype
+            json!({"name": "Detail", "rows": [["North", 10], ["South", 20]]}).to_string(),
+        ]);
+        let workbook = build_workbook(&sheets).unwrap();
+        assert_eq!(read(workbook.clone(), "Summary")[1], vec![Data::Float(30.0)]);
+        assert_eq!(read(workbook.clone(), "Detail").len(), 2);
+
+        let summary = part(&workbook, "xl/worksheets/sheet1.xml");
+        assert!(summary.contains(r#"ySplit="1""#));
+        assert!(summary.contains(r#"width="20.7109375""#));
+        assert!(!part(&workbook, "xl/worksheets/sheet2.xml").contains("ySplit"));
+        assert!(part(&workbook, "xl/styles.xml").contains("<b/>"));
+    }
+
+    #[test]
+    fn errors() {
+        assert!(export("Sheet1", &json!({"a": 1})).is_err());
+        assert!(export("Sheet1", &json!([1, 2])).is_err());
+        assert!(export("bad/name", &json!([])).is_err());
+        assert!(build_workbook(&json!([])).is_err());
+        assert!(build_workbook(&json!([{"rows": []}])).is_err());
+        assert!(build_workbook(&json!([
+            {"name": "a", "rows": [{"x": 1}], "column_widths": {"y": 10}}
+        ]))
+        .is_err());
+    }
+
+    #[test]
+    fn too_many_columns() {
+        let row = |n: usize| Value::Array(vec![json!(1); n]);
+        assert!(export("Sheet1", &json!([row(MAX_COLUMNS)])).is_ok());
+        assert!(export("Sheet1", &json!([row(MAX_COLUMNS + 1)])).is_err());
+        // wider than u16, which would wrap back to column A
+        assert!(export("Sheet1", &json!([row(65_537)])).is_err());
+        let keys: serde_json::Map<String, Value> =
+            (0..=MAX_COLUMNS).map(|i| (format!("c{i}"), json!(i))).collect();
+        assert!(export("Sheet1", &json!([keys])).is_err());
+    }
+}
@@ -0,0 +1,509 @@
+use sqlite_loadable::prelude::*;
+use sqlite_loadable::{
+    api,
+    table::{BestIndexError, ConstraintOperator, IndexInfo, VTab, VTabArguments, VTabCursor},
+    Result,
+};
+use std::{mem, os::raw::c_int};
+
+use calamine::{Data, SheetType};
+use regex::Regex;
+
+use crate::parser::column_idx_to_name;
+use crate::sheet_range::format_address;
+use crate::workbook::Workbook;
+
+static CREATE_SQL: &str = "CREATE TABLE x(sheet, address, row_number, column_name, value, workbook hidden, pattern hidden, in_sheet hidden, mode hidden)";
+enum Columns {
+    Sheet,
+    Address,
+    RowNumber,
+    ColumnName,
+    Value,
+    Workbook,
+    Pattern,
+    InSheet,
+    Mode,
+}
+fn column(index: i32) -> Option<Columns> {
+    match index {
+        0 => Some(Columns::Sheet),
+        1 => Some(Columns::Address),
+        2 => Some(Columns::RowNumber),
+        3 => Some(Columns::ColumnName),
+        4 => Some(Columns::Value),
+        5 => Some(Columns::Workbook),
+        6 => Some(Columns::Pattern),
+        7 => Some(Columns::InSheet),
+        8 => Some(Columns::Mode),
+        _ => None,
+    }
+}
+
+// idx_num flags for the optional arguments passed to filter, in argv order
+const IDX_SHEET: c_int = 0b01;
+const IDX_MODE: c_int = 0b10;
+
+/// One token of a LIKE or GLOB pattern.
+#[derive(Debug, PartialEq)]
+enum Wildcard {
+    /// `%` or `*`: any run of characters
+    Any,
+    /// `_` or `?`: a single character
+    One,
+    /// `[a-z]` or `[^0-9]` in GLOB patterns
+    Class { negated: bool, ranges: Vec<(char, char)> },
+    Literal(char),
+}
+
+/// How cells are compared against the pattern.
+#[derive(Debug)]
+enum Matcher {
+    Exact(String),
+    /// SQL `LIKE`: case-insensitive for ASCII letters
+    Like(Vec<Wildcard>),
+    /// SQL `GLOB`: case-sensitive
+    Glob(Vec<Wildcard>),
+    Regex(Regex),
+}
+
+impl Matcher {
+    fn new(pattern: &str, mode: &str) -> std::result::Result<Matcher, String> {
+        match mode.to_ascii_lowercase().as_str() {
+            "exact" => Ok(Matcher::Exact(pattern.to_owned())),
+            "like" => Ok(Matcher::Like(like_pattern(pattern))),
+            "glob" => Ok(Matcher::Glob(glob_pattern(pattern))),
+            "regex" => Regex::new(pattern)
+                .map(Matcher::Regex)
+                .map_err(|e| format!("invalid regex: {e}")),
+            _ => Err(format!(
+                "unknown mode '{mode}', expected 'exact', 'like', 'glob' or 'regex'"
+            )),
+        }
+    }
+
+    fn is_match(&self, text: &str) -> bool {
+        match self {
+            Matcher::Exact(pattern) => text == pattern,
+            Matcher::Like(tokens) => wildcard_match(tokens, text, true),
+            Matcher::Glob(tokens) => wildcard_match(tokens, text, false),
+            Matcher::Regex(regex) => regex.is_match(text),
+        }
+    }
+}
+
+fn like_pattern(pattern: &str) -> Vec<Wildcard> {
+    pattern
+        .chars()
+        .map(|c| match c {
+            '%' => Wildcard::Any,
+            '_' => Wildcard::One,
+            c => Wildcard::Literal(c),
+        })
+        .collect()
+}
+
+fn glob_pattern(pattern: &str) -> Vec<Wildcard> {
+    let mut tokens = Vec::new();
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' => tokens.push(Wildcard::Any),
+            '?' => tokens.push(Wildcard::One),
+            '[' => {
+                let negated = chars.next_if_eq(&'^').is_some();
+                let mut ranges = Vec::new();
+                // `]` right after the opening bracket is a literal, like in SQLite
+                if let Some(c) = chars.next_if_eq(&']') {
+                    ranges.push((c, c));
+                }
+                let mut closed = false;
+                while let Some(c) = chars.next() {
+                    if c == ']' {
+                        closed = true;
+                        break;
+                    }
+                    let mut ahead = chars.clone();
+                    match (ahead.next(), ahead.next()) {
+                        (Some('-'), Some(end)) if end != ']' => {
+                            chars.next();
+                            chars.next();
+                            ranges.push((c, end));
+                        }
+                        _ => ranges.push((c, c)),
+                    }
+                }
+                if closed {
+                    tokens.push(Wildcard::Class { negated, ranges });
+                } else {
+                    // An unclosed `[` never matches, as in SQLite
+                    tokens.push(Wildcard::Class {
+                        negated: false,
+                        ranges: Vec::new(),
+                    });
+                }
+            }
+            c => tokens.push(Wildcard::Literal(c)),
+        }
+    }
+    tokens
+}
+
+/// Match `text` against a whole LIKE or GLOB pattern, backtracking to the
+/// last `Any` on a mismatch.
+fn wildcard_match(tokens: &[Wildcard], text: &str, ignore_case: bool) -> bool {
+    let text: Vec<char> = text.chars().collect();
+    let matches_one = |token: &Wildcard, c: char| match token {
+        Wildcard::One => true,
+        Wildcard::Literal(l) if ignore_case => l.eq_ignore_ascii_case(&c),
+        Wildcard::Literal(l) => *l == c,
+        Wildcard::Class { negated, ranges } => {
+            ranges.iter().any(|(start, end)| (*start..=*end).contains(&c)) != *negated
+        }
+        Wildcard::Any => false,
+    };
+    let (mut t, mut p) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match tokens.get(p) {
+            Some(Wildcard::Any) => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(token) if matches_one(token, text[t]) => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((star, from)) => {
+                    p = star + 1;
+                    t = from + 1;
+                    backtrack = Some((star, from + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    tokens[p..].iter().all(|token| *token == Wildcard::Any)
+}
+
+/// The text a cell is matched as, the way its value reads in SQL.
+/// Empty cells have none and never match.
+fn cell_text(data: &Data) -> Option<String> {
+    match data {
+        Data::Empty => None,
+        Data::String(value) | Data::DateTimeIso(value) | Data::DurationIso(value) => {
+            Some(value.clone())
+        }
+        Data::Int(value) => Some(value.to_string()),
+        // SQLite shows whole floats with a trailing `.0`
+        Data::Float(value) if value.fract() == 0.0 && value.abs() < 1e15 => {
+            Some(format!("{value:.1}"))
+        }
+        Data::Float(value) => Some(value.to_string()),
+        Data::Bool(value) => Some(if *value { "1" } else { "0" }.to_owned()),
+        Data::DateTime(dt) => Some(crate::xl_datetime_text(dt)),
+        Data::Error(value) => Some(format!("{value}")),
+    }
+}
+
+#[repr(C)]
+pub struct FindTable {
+    /// must be first
+    base: sqlite3_vtab,
+}
+
+impl<'vtab> VTab<'vtab> for FindTable {
+    type Aux = ();
+    type Cursor = FindCursor;
+
+    fn connect(
+        _db: *mut sqlite3,
+        _aux: Option<&Self::Aux>,
+        _args: VTabArguments,
+    ) -> Result<(String, FindTable)> {
+        let base: sqlite3_vtab = unsafe { mem::zeroed() };
+        let vtab = FindTable { base };
+        // TODO db.config(VTabConfig::Innocuous)?;
+        Ok((CREATE_SQL.to_owned(), vtab))
+    }
+    fn destroy(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn best_index(&self, mut info: IndexInfo) -> core::result::Result<(), BestIndexError> {
+        let mut has_workbook = false;
+        let mut has_pattern = false;
+        let mut idx_num = 0;
+        for constraint in info.constraints() {
+            let usable_eq =
+                constraint.usable() && constraint.op() == Some(ConstraintOperator::EQ);
+            match column(constraint.column_idx()) {
+                Some(Columns::Workbook) | Some(Columns::Pattern) if !usable_eq => {
+                    return Err(BestIndexError::Constraint);
+                }
+                Some(Columns::Workbook) => has_workbook = true,
+                Some(Columns::Pattern) => has_pattern = true,
+                Some(Columns::InSheet) if usable_eq => idx_num |= IDX_SHEET,
+                Some(Columns::Mode) if usable_eq => idx_num |= IDX_MODE,
+                _ => (),
+            }
+        }
+        if !has_workbook || !has_pattern {
+            return Err(BestIndexError::Error);
+        }
+        // optional arguments follow the pattern in a fixed order, skipping absent ones
+        let argv_index = |flag: c_int| 3 + (idx_num & (flag - 1)).count_ones() as i32;
+        // only the first constraint on a column is passed on; SQLite checks
+        // any others, such as a second `in_sheet = ?`
+        let mut assigned: Vec<i32> = Vec::new();
+        for mut constraint in info.constraints() {
+            if !(constraint.usable() && constraint.op() == Some(ConstraintOperator::EQ)) {
+                continue;
+            }
+            let argv = match column(constraint.column_idx()) {
+                Some(Columns::Workbook) => 1,
+                Some(Columns::Pattern) => 2,
+                Some(Columns::InSheet) => argv_index(IDX_SHEET),
+                Some(Columns::Mode) => argv_index(IDX_MODE),
+                _ => continue,
+            };
+            if assigned.contains(&argv) {
+                continue;
+            }
+            assigned.push(argv);
+            constraint.set_omit(true);
+            constraint.set_argv_index(argv);
+        }
+        info.set_estimated_cost(100000.0);
+        info.set_estimated_rows(100);
+        info.set_idxnum(idx_num);
+
+        Ok(())
+    }
+
+    fn open(&mut self) -> Result<FindCursor> {
+        Ok(FindCursor::new())
+    }
+}
+
+/// A matching cell: sheet name, 0-based row and column, and value.
+struct Found {
+    sheet: String,
+    row: u32,
+    col: u32,
+    value: Data,
+}
+
+#[repr(C)]
+pub struct FindCursor {
+    /// Base class. Must be first
+    base: sqlite3_vtab_cursor,
+    rowid: i64,
+    values: Option<Vec<Found>>,
+    /// Arguments of the current query, returned by the hidden columns so
+    /// SQLite can check constraints that weren't passed to `filter`
+    workbook: Vec<u8>,
+    pattern: String,
+    in_sheet: Option<String>,
+    mode: Option<String>,
+}
+impl FindCursor {
+    fn new() -> FindCursor {
+        let base: sqlite3_vtab_cursor = unsafe { mem::zeroed() };
+        FindCursor {
+            base,
+            rowid: 0,
+            values: None,
+            workbook: Vec::new(),
+            pattern: String::new(),
+            in_sheet: None,
+            mode: None,
+        }
+    }
+}
+
+impl VTabCursor for FindCursor {
+    fn filter(
+        &mut self,
+        idx_num: c_int,
+        _idx_str: Option<&str>,
+        values: &[*mut sqlite3_value],
+    ) -> Result<()> {
+        let raw = api::value_blob(values.first().expect("1st min constraint is required"));
+        let pattern = api::value_text(values.get(1).expect("2nd min constraint is required"))?;
+        let mut workbook = Workbook::open(raw.to_vec())
+            .map_err(|e| sqlite_loadable::Error::new_message(format!("cannot open workbook: {e}")))?;
+
+        // NULL optional arguments fall back to their defaults
+        let mut args = values.iter().skip(2);
+        let mut optional = |flag: c_int| -> Result<Option<String>> {
+            if idx_num & flag == 0 {
+                return Ok(None);
+            }
+            let value = args.next().unwrap();
+            if matches!(api::value_type(value), api::ValueType::Null) {
+                return Ok(None);
+            }
+            Ok(Some(api::value_text(value)?.to_owned()))
+        };
+        let in_sheet = optional(IDX_SHEET)?;
+        let mode = optional(IDX_MODE)?;
+        let matcher = Matcher::new(pattern, mode.as_deref().unwrap_or("exact"))
+            .map_err(sqlite_loadable::Error::new_message)?;
+
+        let sheets = match in_sheet.clone() {
+            Some(sheet) => vec![sheet],
+            None => workbook
+                .sheets_metadata()
+                .iter()
+                .filter(|sheet| sheet.typ == SheetType::WorkSheet)
+                .map(|sheet| sheet.name.clone())
+                .collect(),
+        };
+        let mut found = Vec::new();
+        for sheet in sheets {
+            let worksheet_range = workbook.worksheet_range(&sheet)
+                .map_err(|_| sqlite_loadable::Error::new_message(format!("sheet '{}' not found", sheet)))?;
+            // calamine positions are (row, col), relative to the start of the range
+            let (start_row, start_col) = worksheet_range.start().unwrap_or((0, 0));
+            for (row, col, value) in worksheet_range.used_cells() {
+                if cell_text(value).is_some_and(|text| matcher.is_match(&text)) {
+                    found.push(Found {
+                        sheet: sheet.clone(),
+                        row: start_row + row as u32,
+                        col: start_col + col as u32,
+                        value: value.to_owned(),
+                    });
+                }
+            }
+        }
+        self.values = Some(found);
+        self.rowid = 0;
+        self.workbook = raw.to_vec();
+        self.pattern = pattern.to_owned();
+        self.in_sheet = in_sheet;
+        self.mode = mode;
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<()> {
+        self.rowid += 1;
+        Ok(())
+    }
+
+    fn eof(&self) -> bool {
+        self.values
+            .as_ref()
+            .unwrap()
+            .get(self.rowid as usize)
+            .is_none()
+    }
+
+    fn column(&self, context: *mut sqlite3_context, i: c_int) -> Result<()> {
+        let v = self
+            .values
+            .as_ref()
+            .unwrap()
+            .get(self.rowid as usize)
+            .unwrap();
+        match column(i) {
+            Some(Columns::Sheet) => api::result_text(context, &v.sheet)?,
+            Some(Columns::Address) => {
+                api::result_text(context, format_address(v.col, v.row, None, false))?
+            }
+            Some(Columns::RowNumber) => api::result_int64(context, i64::from(v.row) + 1),
+            Some(Columns::ColumnName) => api::result_text(context, column_idx_to_name(v.col))?,
+            Some(Columns::Value) => crate::result_xl_data(context, &v.value)?,
+            Some(Columns::Workbook) => api::result_blob(context, &self.workbook),
+            Some(Columns::Pattern) => api::result_text(context, &self.pattern)?,
+            Some(Columns::InSheet) => match &self.in_sheet {
+                Some(sheet) => api::result_text(context, sheet)?,
+                None => api::result_null(context),
+            },
+            Some(Columns::Mode) => match &self.mode {
+                Some(mode) => api::result_text(context, mode)?,
+                None => api::result_null(context),
+            },
+            None => (),
+        }
+        Ok(())
+    }
+
+    fn rowid(&self) -> Result<i64> {
+        Ok(self.rowid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn like(pattern: &str, text: &str) -> bool {
+        Matcher::new(pattern, "like").unwrap().is_match(text)
+    }
+
+    fn glob(pattern: &str, text: &str) -> bool {
+        Matcher::new(pattern, "glob").unwrap().is_match(text)
+    }
+
+    #[test]
+    fn exact() {
+        let matcher = Matcher::new("Grand Total", "exact").unwrap();
+        assert!(matcher.is_match("Grand Total"));
+        assert!(!matcher.is_match("grand total"));
+        assert!(!matcher.is_match("Grand Total:"));
+    }
+
+    #[test]
+    fn like_patterns() {
+        assert!(like("grand total", "Grand Total"));
+        assert!(like("%total%", "Grand Total:"));
+        assert!(like("Q_", "Q1"));
+        assert!(!like("Q_", "Q10"));
+        assert!(like("%", ""));
+        assert!(like("a%b%c", "aXXbYYc"));
+        assert!(!like("a%b%c", "aXXbYY"));
+        assert!(!like("É%", "é"));
+    }
+
+    #[test]
+    fn glob_patterns() {
+        assert!(glob("Grand*", "Grand Total"));
+        assert!(!glob("grand*", "Grand Total"));
+        assert!(glob("Q?", "Q1"));
+        assert!(glob("Q[1-4]", "Q3"));
+        assert!(!glob("Q[1-4]", "Q5"));
+        assert!(glob("Q[^1-4]", "Q5"));
+        assert!(glob("[]]", "]"));
+        assert!(glob("a[-]b", "a-b"));
+        assert!(!glob("[abc", "a"));
+        assert!(glob("*.xlsx", "report.xlsx"));
+    }
+
+    #[test]
+    fn regex() {
+        let matcher = Matcher::new(r"^Total\s+\d{4}$", "regex").unwrap();
+        assert!(matcher.is_match("Total 2024"));
+        assert!(!matcher.is_match("Total: 2024"));
+        assert!(Matcher::new("(", "regex").unwrap_err().starts_with("invalid regex"));
+    }
+
+    #[test]
+    fn unknown_mode() {
+        assert_eq!(
+            Matcher::new("x", "fuzzy").unwrap_err(),
+            "unknown mode 'fuzzy', expected 'exact', 'like', 'glob' or 'regex'"
+        );
+        assert!(Matcher::new("x", "LIKE").is_ok());
+    }
+
+    #[test]
+    fn cell_texts() {
+        assert_eq!(cell_text(&Data::Empty), None);
+        assert_eq!(cell_text(&Data::Float(10.0)), Some("10.0".to_owned()));
+        assert_eq!(cell_text(&Data::Float(10.5)), Some("10.5".to_owned()));
+        assert_eq!(cell_text(&Data::Int(7)), Some("7".to_owned()));
+        assert_eq!(cell_text(&Data::Bool(true)), Some("1".to_owned()));
+    }
+}
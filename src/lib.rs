@@ -1,13 +1,20 @@
+mod cache;
 mod cells;
+mod crypto;
+mod detect;
+mod export;
+mod find;
 mod parser;
+mod patch;
 mod rows;
 mod sheet_range;
 mod sheets;
+mod workbook;
 mod xl0;
 
-use calamine::{Data, DataType};
+use calamine::{Data, ExcelDateTime};
 use parser::column_name_to_idx;
-use sqlite_loadable::table::{define_table_function_with_find, define_virtual_table};
+use sqlite_loadable::table::{define_table_function_with_find, define_virtual_table_writeablex};
 use sqlite_loadable::{api, define_scalar_function, Error, Result};
 use sqlite_loadable::{define_table_function, prelude::*};
 
@@ -21,7 +28,9 @@ pub fn xl_at(context: *mut sqlite3_context, values: &[*mut sqlite3_value]) -> Re
         let idx = match api::value_type(&values[1]) {
           api::ValueType::Integer => api::value_int64(&values[1]),
           api::ValueType::Text => {
-            column_name_to_idx(api::value_text(&values[1])?).unwrap().into()
+            column_name_to_idx(api::value_text(&values[1])?)
+              .map_err(|e| Error::new_message(e.to_string()))?
+              .into()
           }
           _ => todo!(),
         };
@@ -41,36 +50,60 @@ pub fn xl_at(context: *mut sqlite3_context, values: &[*mut sqlite3_value]) -> Re
     Ok(())
 }
 
+/// Format a `Data::DateTime` the way sqlite-xl returns it: `YYYY-MM-DD` for
+/// dates, `HH:MM:SS` for times and durations, and a full timestamp otherwise.
+fn xl_datetime_text(dt: &ExcelDateTime) -> String {
+    if dt.is_duration() {
+        // TimeDelta: format as HH:MM:SS duration
+        let d = dt.as_duration().unwrap();
+        let total_secs = d.num_seconds();
+        let h = total_secs / 3600;
+        let m = (total_secs % 3600) / 60;
+        let s = total_secs % 60;
+        format!("{h:02}:{m:02}:{s:02}")
+    } else {
+        let s = dt.as_datetime().unwrap().to_string();
+        let serial = dt.as_f64();
+        if serial.fract() == 0.0 {
+            // Date-only: strip " 00:00:00" suffix
+            s[..10].to_owned()
+        } else if serial < 1.0 {
+            // Time-only: take just the time part
+            s[11..].to_owned()
+        } else {
+            // Full datetime
+            s
+        }
+    }
+}
+
+/// Whether every cell in a row is empty.
+fn is_blank_row(row: &[Data]) -> bool {
+    row.iter().all(|d| matches!(d, Data::Empty))
+}
+
+/// A cell value as JSON, typed the way `result_xl_data` returns it to SQL.
+fn xl_data_json(data: &Data) -> serde_json::Value {
+    match data {
+        Data::Int(value) => (*value).into(),
+        Data::Float(value) => (*value).into(),
+        Data::String(value) => value.as_str().into(),
+        Data::Bool(value) => (*value).into(),
+        Data::DateTime(dt) => xl_datetime_text(dt).into(),
+        Data::DateTimeIso(value) => value.as_str().into(),
+        Data::DurationIso(value) => value.as_str().into(),
+        Data::Error(value) => format!("{value}").into(),
+        Data::Empty => serde_json::Value::Null,
+    }
+}
+
 fn result_xl_data(context: *mut sqlite3_context, data: &Data) -> Result<()> {
     match data {
         Data::Int(value) => api::result_int64(context, *value),
         Data::Float(value) => api::result_double(context, *value),
         Data::String(value) => api::result_text(context, value)?,
         Data::Bool(value) => api::result_bool(context, *value),
-        Data::DateTime(dt) => {
-            if dt.is_duration() {
-                // TimeDelta: format as HH:MM:SS duration
-                let d = dt.as_duration().unwrap();
-                let total_secs = d.num_seconds();
-                let h = total_secs / 3600;
-                let m = (total_secs % 3600) / 60;
-                let s = total_secs % 60;
-                api::result_text(context, format!("{h:02}:{m:02}:{s:02}"))?;
-            } else {
-                let s = data.as_datetime().unwrap().to_string();
-                let serial = dt.as_f64();
-                if serial.fract() == 0.0 {
-                    // Date-only: strip " 00:00:00" suffix
-                    api::result_text(context, &s[..10])?;
-                } else if serial < 1.0 {
-                    // Time-only: take just the time part
-                    api::result_text(context, &s[11..])?;
-                } else {
-                    // Full datetime
-                    api::result_text(context, s)?;
-                }
-            }
-        }
+        Data::DateTime(dt) => api::result_text(context, xl_datetime_text(dt))?,
         Data::DateTimeIso(value) => api::result_text(context, value)?,
         Data::DurationIso(value) => api::result_text(context, value)?,
         Data::Error(value) => {
@@ -89,7 +122,7 @@ pub fn xl_version(context: *mut sqlite3_context, _values: &[*mut sqlite3_value])
 pub fn xl_valid(context: *mut sqlite3_context, values: &[*mut sqlite3_value]) -> Result<()> {
     let raw = api::value_blob(&values[0]);
     let data = raw.to_vec();
-    match calamine::open_workbook_auto_from_rs::<_>(std::io::Cursor::new(data)) {
+    match workbook::Workbook::open(data) {
         Ok(_) => api::result_bool(context, true),
         Err(_) => api::result_bool(context, false),
     }
@@ -100,11 +133,42 @@ pub fn xl_valid(context: *mut sqlite3_context, values: &[*mut sqlite3_value]) ->
 pub fn sqlite3_xl_init(db: *mut sqlite3) -> Result<()> {
     define_table_function::<sheets::SheetsTable>(db, "xl_sheets", None)?;
     define_table_function::<cells::CellsTable>(db, "xl_cells", None)?;
+    define_table_function::<find::FindTable>(db, "xl_find", None)?;
     define_table_function_with_find::<rows::RowsTable>(db, "xl_rows", None)?;
     define_scalar_function(db, "xl_at", 2, xl_at, FunctionFlags::UTF8)?;
+    define_scalar_function(db, "xl_r1c1_to_a1", 1, sheet_range::xl_r1c1_to_a1, FunctionFlags::UTF8)?;
+    define_scalar_function(db, "xl_quote_sheet", 1, sheet_range::xl_quote_sheet, FunctionFlags::UTF8)?;
+    define_scalar_function(db, "xl_column_name", 1, sheet_range::xl_column_name, FunctionFlags::UTF8)?;
+    define_scalar_function(db, "xl_column_index", 1, sheet_range::xl_column_index, FunctionFlags::UTF8)?;
+    define_scalar_function(db, "xl_address", 2, sheet_range::xl_address, FunctionFlags::UTF8)?;
+    define_scalar_function(db, "xl_address", 3, sheet_range::xl_address, FunctionFlags::UTF8)?;
+    define_scalar_function(db, "xl_address", 4, sheet_range::xl_address, FunctionFlags::UTF8)?;
+    define_scalar_function(db, "xl_parse_range", 1, sheet_range::xl_parse_range, FunctionFlags::UTF8)?;
+    define_scalar_function(db, "xl_range_contains", 2, sheet_range::xl_range_contains, FunctionFlags::UTF8)?;
+    define_scalar_function(db, "xl_range_intersect", 2, sheet_range::xl_range_intersect, FunctionFlags::UTF8)?;
+    define_scalar_function(db, "xl_offset", 3, sheet_range::xl_offset, FunctionFlags::UTF8)?;
+    define_scalar_function(db, "xl_offset", 4, sheet_range::xl_offset, FunctionFlags::UTF8)?;
+    define_scalar_function(db, "xl_offset", 5, sheet_range::xl_offset, FunctionFlags::UTF8)?;
+    define_scalar_function(db, "xl_range_size", 1, sheet_range::xl_range_size, FunctionFlags::UTF8)?;
     define_scalar_function(db, "xl_version", 0, xl_version, FunctionFlags::UTF8)?;
     define_scalar_function(db, "xl_valid", 1, xl_valid, FunctionFlags::UTF8)?;
-    define_virtual_table::<xl0::XL0Table>(db, "xl0", None)?;
+    define_scalar_function(db, "xl_extract", 2, cells::xl_extract, FunctionFlags::UTF8)?;
+    define_scalar_function(db, "xl_decrypt", 2, crypto::xl_decrypt, FunctionFlags::UTF8)?;
+    define_scalar_function(db, "xl_format", 1, workbook::xl_format, FunctionFlags::UTF8)?;
+    define_scalar_function(db, "xl_validate", 1, workbook::xl_validate, FunctionFlags::UTF8)?;
+    define_scalar_function(db, "xl_detect_table", 1, detect::xl_detect_table, FunctionFlags::UTF8)?;
+    define_scalar_function(db, "xl_detect_table", 2, detect::xl_detect_table, FunctionFlags::UTF8)?;
+    export::define_xl_export(db)?;
+    define_scalar_function(db, "xl_sheet", 2, export::xl_sheet, FunctionFlags::UTF8)?;
+    define_scalar_function(db, "xl_sheet", 3, export::xl_sheet, FunctionFlags::UTF8)?;
+    define_scalar_function(db, "xl_workbook", 1, export::xl_workbook, FunctionFlags::UTF8)?;
+    define_scalar_function(db, "xl_set", -1, patch::xl_set, FunctionFlags::UTF8)?;
+    define_scalar_function(db, "xl_import", 3, xl0::xl_import, FunctionFlags::UTF8)?;
+    define_scalar_function(db, "xl_import", 4, xl0::xl_import, FunctionFlags::UTF8)?;
+    define_scalar_function(db, "xl_schema", 2, xl0::xl_schema, FunctionFlags::UTF8)?;
+    define_scalar_function(db, "xl_schema", 3, xl0::xl_schema, FunctionFlags::UTF8)?;
+    define_scalar_function(db, "xl_schema", 4, xl0::xl_schema, FunctionFlags::UTF8)?;
+    define_virtual_table_writeablex::<xl0::XL0Table>(db, "xl0", None)?;
     Ok(())
 }
 
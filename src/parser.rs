@@ -18,36 +18,90 @@ enum Reference {
     Range((usize, usize)),
 }
 
-#[allow(dead_code)]
+/// The size of a worksheet in a given file format.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SheetLimits {
+    pub max_columns: u32,
+    pub max_rows: u32,
+}
+
+impl SheetLimits {
+    /// `.xlsx`, `.xlsm`, `.xlsb` and `.ods`: columns up to `XFD`, 1,048,576 rows
+    pub const XLSX: SheetLimits = SheetLimits {
+        max_columns: 16_384,
+        max_rows: 1_048_576,
+    };
+    /// Legacy `.xls`: columns up to `IV`, 65,536 rows
+    pub const XLS: SheetLimits = SheetLimits {
+        max_columns: 256,
+        max_rows: 65_536,
+    };
+
+    /// Check a 0-based column index against these limits.
+    pub fn check_column(&self, col: u32) -> Result<(), LocationError> {
+        if col >= self.max_columns {
+            return Err(LocationError::ColumnOutOfRange {
+                column: column_idx_to_name(col),
+                last: column_idx_to_name(self.max_columns - 1),
+            });
+        }
+        Ok(())
+    }
+
+    /// Check a 0-based row index against these limits.
+    pub fn check_row(&self, row: u32) -> Result<(), LocationError> {
+        if row >= self.max_rows {
+            return Err(LocationError::RowOutOfRange {
+                row: u64::from(row) + 1,
+                last: self.max_rows,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Why a column name or row number doesn't name a cell of a worksheet.
+#[derive(Error, Debug, PartialEq, Clone)]
+pub enum LocationError {
+    #[error("invalid column name '{0}'")]
+    InvalidColumn(String),
+    #[error("column {column} is past the last column {last}")]
+    ColumnOutOfRange { column: String, last: String },
+    #[error("row {row} is outside rows 1 to {last}")]
+    RowOutOfRange { row: u64, last: u32 },
+}
+
 #[derive(Error, Debug, PartialEq)]
 pub enum ParseCellReferenceError<'a> {
-    #[error("the data for key `{0}` is not available")]
-    Redaction(String),
-    #[error("idk")]
-    Unknown,
     #[error("Empty input")]
     Empty,
-
     #[error("Syntax error: {0}")]
     Syntax(&'a str),
+    #[error(transparent)]
+    Location(#[from] LocationError),
 }
 
-pub fn column_name_to_idx(column_name: &str) -> Result<u32, ()> {
+/// 0-based index of a column name like `A`, `ab` or `XFD`.
+pub fn column_name_to_idx(column_name: &str) -> Result<u32, LocationError> {
+    if column_name.is_empty() || !column_name.chars().all(|c| c.is_ascii_alphabetic()) {
+        return Err(LocationError::InvalidColumn(column_name.to_owned()));
+    }
+    let out_of_range = || LocationError::ColumnOutOfRange {
+        column: column_name.to_ascii_uppercase(),
+        last: column_idx_to_name(SheetLimits::XLSX.max_columns - 1),
+    };
     let mut value: u32 = 0;
-    let mut multiplier = 1;
-    for c in column_name.chars().rev() {
-        if c.is_ascii_lowercase() {
-            let v: u32 = c as u32 - 'a' as u32 + 1;
-            value += v * multiplier;
-        } else if c.is_ascii_uppercase() {
-            let v: u32 = c as u32 - 'A' as u32 + 1;
-            value += v * multiplier;
-        } else {
-            todo!();
-        }
-        multiplier *= 26;
+    for c in column_name.chars() {
+        let v = c.to_ascii_uppercase() as u32 - 'A' as u32 + 1;
+        value = value
+            .checked_mul(26)
+            .and_then(|value| value.checked_add(v))
+            .ok_or_else(out_of_range)?;
+    }
+    if value > SheetLimits::XLSX.max_columns {
+        return Err(out_of_range());
     }
-    Ok(value  - 1)
+    Ok(value - 1)
 }
 
 pub fn column_idx_to_name(idx: u32) -> String {
@@ -61,13 +115,24 @@ pub fn column_idx_to_name(idx: u32) -> String {
     name
 }
 
-fn cell_location(column: &str, row: u32) -> Result<(u32, u32), ()> {
-    Ok((column_name_to_idx(column)?, row - 1))
+/// 0-based index of a 1-based row number, within the limits of an `.xlsx` sheet.
+pub fn row_number_to_idx(row: u64) -> Result<u32, LocationError> {
+    match u32::try_from(row) {
+        Ok(row) if (1..=SheetLimits::XLSX.max_rows).contains(&row) => Ok(row - 1),
+        _ => Err(LocationError::RowOutOfRange {
+            row,
+            last: SheetLimits::XLSX.max_rows,
+        }),
+    }
+}
+
+fn cell_location(column: &str, row: u64) -> Result<(u32, u32), LocationError> {
+    Ok((column_name_to_idx(column)?, row_number_to_idx(row)?))
 }
 
-#[allow(dead_code)]
 pub fn parse_cell_reference(input: &str) -> Result<CellReference, ParseCellReferenceError<'_>> {
     let tokens = tokenize(input);
+    check_tokens(&tokens)?;
     let mut tokens = tokens.iter();
     let first = tokens.next().ok_or(ParseCellReferenceError::Empty)?;
     let second = tokens
@@ -75,7 +140,7 @@ pub fn parse_cell_reference(input: &str) -> Result<CellReference, ParseCellRefer
         .ok_or(ParseCellReferenceError::Syntax("expected row value"))?;
     let column = match first {
         Token::Identifier(id) => id,
-        _ => return Err(ParseCellReferenceError::Syntax("asdf")),
+        _ => return Err(ParseCellReferenceError::Syntax("expected a column name")),
     };
     let row = match second {
         Token::Number(n) => n,
@@ -87,22 +152,23 @@ pub fn parse_cell_reference(input: &str) -> Result<CellReference, ParseCellRefer
     };
 
     Ok(CellReference {
-        location: cell_location(column, *row).map_err(|_| ParseCellReferenceError::Unknown)?,
+        location: cell_location(column, *row)?,
     })
 }
 
 #[derive(Error, Debug, PartialEq)]
 pub enum ParseRangeReferenceError<'a> {
-    #[error("idk")]
-    Unknown,
     #[error("Empty input")]
     Empty,
     #[error("Syntax error: {0}")]
     Syntax(&'a str),
+    #[error(transparent)]
+    Location(#[from] LocationError),
 }
 
 pub fn parse_range_reference(input: &str) -> Result<RangeReference, ParseRangeReferenceError<'_>> {
     let tokens = tokenize(input);
+    check_tokens(&tokens)?;
     let mut tokens = tokens.iter();
     let first = tokens.next().ok_or(ParseRangeReferenceError::Empty)?;
     let second = tokens
@@ -111,7 +177,7 @@ pub fn parse_range_reference(input: &str) -> Result<RangeReference, ParseRangeRe
 
     let c1 = match first {
         Token::Identifier(id) => id,
-        _ => return Err(ParseRangeReferenceError::Syntax("asdf")),
+        _ => return Err(ParseRangeReferenceError::Syntax("expected a column name")),
     };
     let r1 = match second {
         Token::Number(n) => n,
@@ -123,7 +189,7 @@ pub fn parse_range_reference(input: &str) -> Result<RangeReference, ParseRangeRe
     };
     let third = tokens.next().ok_or(ParseRangeReferenceError::Empty)?;
     if !matches!(third, Token::RangeOperator) {
-        return Err(ParseRangeReferenceError::Syntax("asdf"));
+        return Err(ParseRangeReferenceError::Syntax("expected ':' between cells"));
     }
     let fourth = tokens.next().ok_or(ParseRangeReferenceError::Empty)?;
     let fifth = tokens
@@ -132,7 +198,7 @@ pub fn parse_range_reference(input: &str) -> Result<RangeReference, ParseRangeRe
 
     let c2 = match fourth {
         Token::Identifier(id) => id,
-        _ => return Err(ParseRangeReferenceError::Syntax("asdf")),
+        _ => return Err(ParseRangeReferenceError::Syntax("expected a column name")),
     };
     let r2 = match fifth {
         Token::Number(n) => n,
@@ -144,15 +210,25 @@ pub fn parse_range_reference(input: &str) -> Result<RangeReference, ParseRangeRe
     };
 
     Ok(RangeReference {
-        start: cell_location(c1, *r1).map_err(|_| ParseRangeReferenceError::Unknown)?,
-        end: cell_location(c2, *r2).map_err(|_| ParseRangeReferenceError::Unknown)?,
+        start: cell_location(c1, *r1)?,
+        end: cell_location(c2, *r2)?,
     })
 }
 
+/// Reject letters that can't be part of a column name, like `é` in `é1`.
+fn check_tokens(tokens: &[Token]) -> Result<(), LocationError> {
+    match tokens.iter().find(|t| matches!(t, Token::Invalid(_))) {
+        Some(Token::Invalid(letters)) => Err(LocationError::InvalidColumn(letters.clone())),
+        _ => Ok(()),
+    }
+}
+
 #[derive(Debug, PartialEq)]
 enum Token {
     Identifier(String),
-    Number(u32),
+    /// Letters outside `A-Z`, which can't be part of a column name
+    Invalid(String),
+    Number(u64),
     // ':'
     RangeOperator,
 }
@@ -166,11 +242,11 @@ fn tokenize(input: &str) -> Vec<Token> {
     let mut tokens: Vec<Token> = Vec::new();
     let mut chars = input.chars().peekable();
     while let Some(c) = chars.next() {
-        if c.is_alphabetic() {
+        if c.is_ascii_alphabetic() {
             let mut value = String::new();
             value.push(c);
             while let Some(c) = chars.peek() {
-                if c.is_alphabetic() {
+                if c.is_ascii_alphabetic() {
                     value.push(*c);
                     chars.next();
                     continue;
@@ -178,18 +254,26 @@ fn tokenize(input: &str) -> Vec<Token> {
                 break;
             }
             tokens.push(Token::Identifier(value));
-        } else if c.is_numeric() {
+        } else if c.is_alphabetic() {
+            let mut value = String::new();
+            value.push(c);
+            while let Some(c) = chars.next_if(|c| c.is_alphabetic() && !c.is_ascii()) {
+                value.push(c);
+            }
+            tokens.push(Token::Invalid(value));
+        } else if c.is_ascii_digit() {
             let mut value = String::new();
             value.push(c);
             while let Some(c) = chars.peek() {
-                if c.is_numeric() {
+                if c.is_ascii_digit() {
                     value.push(*c);
                     chars.next();
                     continue;
                 }
                 break;
             }
-            tokens.push(Token::Number(value.parse::<u32>().unwrap()));
+            // Too many digits for any row; reported as out of range
+            tokens.push(Token::Number(value.parse::<u64>().unwrap_or(u64::MAX)));
         } else if c == ':' {
             tokens.push(Token::RangeOperator);
         }
@@ -225,6 +309,14 @@ mod tests {
                 Token::Number(3)
             ]
         );
+        assert_eq!(
+            tokenize("Aé1"),
+            vec![
+                Token::Identifier("A".to_string()),
+                Token::Invalid("é".to_string()),
+                Token::Number(1)
+            ]
+        );
     }
 
     #[test]
@@ -258,13 +350,68 @@ mod tests {
             Ok(CellReference { location: (27, 0) })
         );
         assert_eq!(
-            parse_cell_reference("zfD99999"),
+            parse_cell_reference("xfD1048576"),
             Ok(CellReference {
-                location: (17735, 99998)
+                location: (16383, 1048575)
             })
         );
     }
 
+    #[test]
+    fn test_reference_limits() {
+        assert_eq!(
+            parse_cell_reference("XFE1"),
+            Err(ParseCellReferenceError::Location(LocationError::ColumnOutOfRange {
+                column: "XFE".to_owned(),
+                last: "XFD".to_owned()
+            }))
+        );
+        assert_eq!(
+            parse_cell_reference("A1048577"),
+            Err(ParseCellReferenceError::Location(LocationError::RowOutOfRange {
+                row: 1048577,
+                last: 1048576
+            }))
+        );
+        assert_eq!(
+            parse_cell_reference("A0"),
+            Err(ParseCellReferenceError::Location(LocationError::RowOutOfRange {
+                row: 0,
+                last: 1048576
+            }))
+        );
+        assert!(matches!(
+            parse_cell_reference("A99999999999999999999999"),
+            Err(ParseCellReferenceError::Location(LocationError::RowOutOfRange { .. }))
+        ));
+        assert!(matches!(
+            parse_range_reference("A1:ZZZZZZZZZZZZ2"),
+            Err(ParseRangeReferenceError::Location(LocationError::ColumnOutOfRange { .. }))
+        ));
+        assert_eq!(
+            parse_cell_reference("é1"),
+            Err(ParseCellReferenceError::Location(LocationError::InvalidColumn("é".to_owned())))
+        );
+    }
+
+    #[test]
+    fn test_sheet_limits() {
+        assert_eq!(SheetLimits::XLS.check_column(255), Ok(()));
+        assert_eq!(
+            SheetLimits::XLS.check_column(256),
+            Err(LocationError::ColumnOutOfRange {
+                column: "IW".to_owned(),
+                last: "IV".to_owned()
+            })
+        );
+        assert_eq!(SheetLimits::XLS.check_row(65535), Ok(()));
+        assert_eq!(
+            SheetLimits::XLS.check_row(65536).unwrap_err().to_string(),
+            "row 65537 is outside rows 1 to 65536"
+        );
+        assert_eq!(SheetLimits::XLSX.check_row(65536), Ok(()));
+    }
+
     #[test]
     fn test_parse_range_reference() {
         assert_eq!(
@@ -280,11 +427,18 @@ mod tests {
     fn test_column_parsing() {
       assert_eq!(cell_location("A", 1), Ok((0, 0)));
       assert_eq!(cell_location("Z", 100), Ok((25, 99)));
+      assert_eq!(cell_location("Z", 0), Err(LocationError::RowOutOfRange { row: 0, last: 1048576 }));
       assert_eq!(column_name_to_idx("A"), Ok(0));
       assert_eq!(column_name_to_idx("a"), Ok(0));
       assert_eq!(column_name_to_idx("Z"), Ok(25));
       assert_eq!(column_name_to_idx("z"), Ok(25));
       assert_eq!(column_name_to_idx("AA"), Ok(26));
-      assert_eq!(column_name_to_idx("ZFD"), Ok(17735));
+      assert_eq!(column_name_to_idx("XFD"), Ok(16383));
+      assert_eq!(
+          column_name_to_idx("ZFD"),
+          Err(LocationError::ColumnOutOfRange { column: "ZFD".to_owned(), last: "XFD".to_owned() })
+      );
+      assert_eq!(column_name_to_idx(""), Err(LocationError::InvalidColumn(String::new())));
+      assert_eq!(column_name_to_idx("A1"), Err(LocationError::InvalidColumn("A1".to_owned())));
     }
 }
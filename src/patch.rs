@@ -0,0 +1,641 @@
+//! Write cell values into an existing `.xlsx` workbook.
+//!
+//! Only the XML of the edited sheet is rewritten: every other part of the
+//! package, and every cell that isn't edited, is copied through unchanged.
+
+use std::collections::BTreeMap;
+use std::io::{Cursor, Read, Write};
+
+use calamine::Data;
+use quick_xml::escape::unescape;
+use quick_xml::events::attributes::Attribute;
+use quick_xml::events::{BytesEnd, BytesStart, BytesText, Event};
+use quick_xml::{Reader, Writer};
+use sqlite_loadable::prelude::*;
+use sqlite_loadable::{api, Error, Result};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::parser::{column_idx_to_name, parse_cell_reference, parse_range_reference};
+use crate::sheet_range::{parse_sheet_reference, SheetTarget};
+
+/// Cell values to write, keyed by 0-based (row, column).
+pub type CellEdits = BTreeMap<(u32, u32), Data>;
+
+fn xml_error(e: impl std::fmt::Display) -> Error {
+    Error::new_message(format!("invalid workbook XML: {e}"))
+}
+
+fn zip_error(e: impl std::fmt::Display) -> Error {
+    Error::new_message(format!("cannot write workbook: {e}"))
+}
+
+/// Unescaped value of an attribute. Package parts are always UTF-8.
+fn attribute_value(attr: &Attribute) -> Result<String> {
+    let raw = std::str::from_utf8(&attr.value).map_err(xml_error)?;
+    Ok(unescape(raw).map_err(xml_error)?.into_owned())
+}
+
+/// Value of an attribute by name, unescaped.
+fn attribute(e: &BytesStart, name: &str) -> Result<Option<String>> {
+    e.try_get_attribute(name)
+        .map_err(xml_error)?
+        .map(|a| attribute_value(&a))
+        .transpose()
+}
+
+fn read_entry<R: Read + std::io::Seek>(archive: &mut ZipArchive<R>, name: &str) -> Result<String> {
+    let mut file = archive
+        .by_name(name)
+        .map_err(|_| Error::new_message(format!("workbook is missing '{name}'")))?;
+    let mut xml = String::new();
+    file.read_to_string(&mut xml)
+        .map_err(|e| Error::new_message(format!("cannot read '{name}': {e}")))?;
+    Ok(xml)
+}
+
+/// Path of a sheet's XML part inside the package, e.g. `xl/worksheets/sheet1.xml`.
+fn sheet_path<R: Read + std::io::Seek>(archive: &mut ZipArchive<R>, sheet: &str) -> Result<String> {
+    let workbook = read_entry(archive, "xl/workbook.xml")?;
+    let mut reader = Reader::from_str(&workbook);
+    let mut rel_id = None;
+    loop {
+        match reader.read_event().map_err(xml_error)? {
+            Event::Eof => break,
+            Event::Empty(e) | Event::Start(e)
+                if e.local_name().as_ref() == b"sheet"
+                    && attribute(&e, "name")?.as_deref() == Some(sheet) =>
+            {
+                for attr in e.attributes() {
+                    let attr = attr.map_err(xml_error)?;
+                    if attr.key.local_name().as_ref() == b"id" && attr.key.prefix().is_some() {
+                        rel_id = Some(attribute_value(&attr)?);
+                    }
+                }
+                break;
+            }
+            _ => {}
+        }
+    }
+    let rel_id = rel_id.ok_or_else(|| Error::new_message(format!("sheet '{sheet}' not found")))?;
+
+    let rels = read_entry(archive, "xl/_rels/workbook.xml.rels")?;
+    let mut reader = Reader::from_str(&rels);
+    loop {
+        match reader.read_event().map_err(xml_error)? {
+            Event::Eof => break,
+            Event::Empty(e) | Event::Start(e)
+                if e.local_name().as_ref() == b"Relationship"
+                    && attribute(&e, "Id")?.as_deref() == Some(rel_id.as_str()) =>
+            {
+                let target = attribute(&e, "Target")?
+                    .ok_or_else(|| xml_error("relationship without a target"))?;
+                return Ok(match target.strip_prefix('/') {
+                    Some(absolute) => absolute.to_owned(),
+                    None => format!("xl/{target}"),
+                });
+            }
+            _ => {}
+        }
+    }
+    Err(Error::new_message(format!("sheet '{sheet}' not found")))
+}
+
+/// A1 address of a 0-based cell position, for `r` attributes and errors.
+fn cell_address(row: u32, col: u32) -> String {
+    format!("{}{}", column_idx_to_name(col), row + 1)
+}
+
+/// Events of a new `<c>` element holding `data`, keeping the cell's style.
+fn cell_events(row: u32, col: u32, style: Option<&str>, data: &Data) -> Result<Vec<Event<'static>>> {
+    let mut start = BytesStart::new("c");
+    start.push_attribute(("r", cell_address(row, col).as_str()));
+    if let Some(style) = style {
+        start.push_attribute(("s", style));
+    }
+    let (kind, value) = match data {
+        Data::Empty => {
+            return Ok(match style {
+                Some(_) => vec![Event::Empty(start)],
+                None => vec![],
+            })
+        }
+        Data::Int(v) => (None, v.to_string()),
+        Data::Float(v) if !v.is_finite() => {
+            return Err(Error::new_message(format!(
+                "cannot write {v} into {}: cells only hold finite numbers",
+                cell_address(row, col)
+            )))
+        }
+        Data::Float(v) => (None, v.to_string()),
+        Data::DateTime(dt) => (None, dt.as_f64().to_string()),
+        Data::Bool(v) => (Some("b"), if *v { "1" } else { "0" }.to_owned()),
+        Data::Error(e) => (Some("e"), e.to_string()),
+        Data::String(s) | Data::DateTimeIso(s) | Data::DurationIso(s) => {
+            start.push_attribute(("t", "inlineStr"));
+            let mut text = BytesStart::new("t");
+            if s.trim() != s {
+                text.push_attribute(("xml:space", "preserve"));
+            }
+            return Ok(vec![
+                Event::Start(start),
+                Event::Start(BytesStart::new("is")),
+                Event::Start(text),
+                Event::Text(BytesText::new(s).into_owned()),
+                Event::End(BytesEnd::new("t")),
+                Event::End(BytesEnd::new("is")),
+                Event::End(BytesEnd::new("c")),
+            ]);
+        }
+    };
+    if let Some(kind) = kind {
+        start.push_attribute(("t", kind));
+    }
+    Ok(vec![
+        Event::Start(start),
+        Event::Start(BytesStart::new("v")),
+        Event::Text(BytesText::new(&value).into_owned()),
+        Event::End(BytesEnd::new("v")),
+        Event::End(BytesEnd::new("c")),
+    ])
+}
+
+/// Whether a cell's events hold the master of a shared formula,
+/// `<f t="shared" ref="…">`, which the other cells of `ref` take their
+/// formula from.
+fn is_shared_formula_master(events: &[Event]) -> Result<bool> {
+    for event in events {
+        if let Event::Start(e) | Event::Empty(e) = event {
+            if e.local_name().as_ref() == b"f"
+                && attribute(e, "t")?.as_deref() == Some("shared")
+                && attribute(e, "ref")?.is_some()
+            {
+                return Ok(true);
+            }
+        }
+    }
+    Ok(false)
+}
+
+/// A `<row>` start tag for row `row`, without the optional `spans` hint,
+/// which may no longer be accurate once cells are added.
+fn row_start(existing: Option<&BytesStart>, row: u32) -> Result<BytesStart<'static>> {
+    let mut start = BytesStart::new("row");
+    start.push_attribute(("r", (row + 1).to_string().as_str()));
+    if let Some(existing) = existing {
+        for attr in existing.attributes() {
+            let attr = attr.map_err(xml_error)?;
+            if !matches!(attr.key.as_ref(), b"r" | b"spans") {
+                start.push_attribute(attr);
+            }
+        }
+    }
+    Ok(start.into_owned())
+}
+
+/// A cell read from an existing row: its column, style and raw events.
+struct ExistingCell<'a> {
+    col: u32,
+    style: Option<String>,
+    events: Vec<Event<'a>>,
+}
+
+/// Write a row from its existing cells and the edits for that row.
+fn write_row(
+    writer: &mut Writer<Vec<u8>>,
+    start: BytesStart<'static>,
+    row: u32,
+    existing: Vec<ExistingCell>,
+    edits: &BTreeMap<u32, &Data>,
+) -> Result<()> {
+    let mut cells: BTreeMap<u32, Vec<Event>> = BTreeMap::new();
+    let mut styles: BTreeMap<u32, Option<String>> = BTreeMap::new();
+    for cell in existing {
+        styles.insert(cell.col, cell.style);
+        cells.insert(cell.col, cell.events);
+    }
+    for (col, data) in edits {
+        // the formula of the cells sharing it would be lost with the master
+        if let Some(events) = cells.get(col) {
+            if is_shared_formula_master(events)? {
+                return Err(Error::new_message(format!(
+                    "cannot write {}: other cells share its formula",
+                    cell_address(row, *col)
+                )));
+            }
+        }
+        let style = styles.get(col).cloned().flatten();
+        cells.insert(*col, cell_events(row, *col, style.as_deref(), data)?);
+    }
+    let events: Vec<Event> = cells.into_values().flatten().collect();
+    if events.is_empty() {
+        writer.write_event(Event::Empty(start)).map_err(xml_error)?;
+    } else {
+        writer.write_event(Event::Start(start)).map_err(xml_error)?;
+        for event in events {
+            writer.write_event(event).map_err(xml_error)?;
+        }
+        writer.write_event(Event::End(BytesEnd::new("row"))).map_err(xml_error)?;
+    }
+    Ok(())
+}
+
+/// The `<dimension>` reference grown to cover the edited cells.
+fn grow_dimension(current: &str, edits: &CellEdits) -> String {
+    let (mut start, mut end) = match parse_range_reference(current) {
+        Ok(r) => (r.start, r.end),
+        Err(_) => match parse_cell_reference(current) {
+            Ok(c) => (c.location, c.location),
+            Err(_) => return current.to_owned(),
+        },
+    };
+    for (row, col) in edits.keys() {
+        start = (start.0.min(*col), start.1.min(*row));
+        end = (end.0.max(*col), end.1.max(*row));
+    }
+    format!(
+        "{}{}:{}{}",
+        column_idx_to_name(start.0),
+        start.1 + 1,
+        column_idx_to_name(end.0),
+        end.1 + 1
+    )
+}
+
+/// 0-based row of a `<row>` element; rows without `r` follow the previous one.
+fn row_number(e: &BytesStart, last_row: Option<u32>) -> Result<u32> {
+    match attribute(e, "r")? {
+        Some(r) => r
+            .parse::<u32>()
+            .ok()
+            .and_then(|r| r.checked_sub(1))
+            .ok_or_else(|| xml_error(format!("invalid row number '{r}'"))),
+        None => Ok(last_row.map_or(0, |r| r + 1)),
+    }
+}
+
+/// Read the cells of a `<row>` up to its end tag.
+fn read_cells<'a>(reader: &mut Reader<&'a [u8]>) -> Result<Vec<ExistingCell<'a>>> {
+    let mut cells: Vec<ExistingCell> = Vec::new();
+    loop {
+        let event = reader.read_event().map_err(xml_error)?;
+        match event {
+            Event::End(e) if e.local_name().as_ref() == b"row" => break,
+            Event::Eof => return Err(xml_error("unexpected end of worksheet")),
+            Event::Start(ref e) | Event::Empty(ref e) if e.local_name().as_ref() == b"c" => {
+                let col = match attribute(e, "r")? {
+                    Some(r) => {
+                        parse_cell_reference(&r)
+                            .map_err(|_| xml_error(format!("invalid cell reference '{r}'")))?
+                            .location
+                            .0
+                    }
+                    None => cells.last().map_or(0, |c| c.col + 1),
+                };
+                let style = attribute(e, "s")?;
+                let is_start = matches!(event, Event::Start(_));
+                let mut events = vec![event];
+                if is_start {
+                    loop {
+                        let inner = reader.read_event().map_err(xml_error)?;
+                        let done = matches!(&inner, Event::End(e) if e.local_name().as_ref() == b"c");
+                        if matches!(inner, Event::Eof) {
+                            return Err(xml_error("unexpected end of worksheet"));
+                        }
+                        events.push(inner);
+                        if done {
+                            break;
+                        }
+                    }
+                }
+                cells.push(ExistingCell { col, style, events });
+            }
+            // whitespace between cells
+            Event::Text(_) => {}
+            // anything else, such as extLst, stays after the cells
+            other => match cells.last_mut() {
+                Some(cell) if cell.col == u32::MAX => cell.events.push(other),
+                _ => cells.push(ExistingCell {
+                    col: u32::MAX,
+                    style: None,
+                    events: vec![other],
+                }),
+            },
+        }
+    }
+    Ok(cells)
+}
+
+/// Apply cell edits to the XML of a worksheet.
+fn patch_sheet_xml(xml: &str, edits: &CellEdits) -> Result<Vec<u8>> {
+    let mut by_row: BTreeMap<u32, BTreeMap<u32, &Data>> = BTreeMap::new();
+    for ((row, col), data) in edits {
+        by_row.entry(*row).or_default().insert(*col, data);
+    }
+    let mut pending = by_row.into_iter().peekable();
+
+    let mut reader = Reader::from_str(xml);
+    let mut writer = Writer::new(Vec::new());
+    let mut in_sheet_data = false;
+    let mut last_row: Option<u32> = None;
+
+    loop {
+        let event = reader.read_event().map_err(xml_error)?;
+        match event {
+            Event::Eof => break,
+            Event::Empty(e) if e.local_name().as_ref() == b"dimension" => {
+                let mut dimension = BytesStart::new("dimension");
+                for attr in e.attributes() {
+                    let attr = attr.map_err(xml_error)?;
+                    if attr.key.as_ref() == b"ref" {
+                        let current = attribute_value(&attr)?;
+                        dimension.push_attribute(("ref", grow_dimension(&current, edits).as_str()));
+                    } else {
+                        dimension.push_attribute(attr);
+                    }
+                }
+                writer.write_event(Event::Empty(dimension)).map_err(xml_error)?;
+            }
+            Event::Empty(e) if e.local_name().as_ref() == b"sheetData" => {
+                let end = e.to_end().into_owned();
+                writer.write_event(Event::Start(e)).map_err(xml_error)?;
+                for (row, row_edits) in pending.by_ref() {
+                    write_row(&mut writer, row_start(None, row)?, row, Vec::new(), &row_edits)?;
+                }
+                writer.write_event(Event::End(end)).map_err(xml_error)?;
+            }
+            Event::Start(e) if e.local_name().as_ref() == b"sheetData" => {
+                in_sheet_data = true;
+                writer.write_event(Event::Start(e)).map_err(xml_error)?;
+            }
+            Event::End(e) if e.local_name().as_ref() == b"sheetData" => {
+                for (row, row_edits) in pending.by_ref() {
+                    write_row(&mut writer, row_start(None, row)?, row, Vec::new(), &row_edits)?;
+                }
+                in_sheet_data = false;
+                writer.write_event(Event::End(e)).map_err(xml_error)?;
+            }
+            Event::Start(ref e) | Event::Empty(ref e)
+                if in_sheet_data && e.local_name().as_ref() == b"row" =>
+            {
+                let row = row_number(e, last_row)?;
+                last_row = Some(row);
+                // new rows that come before this one
+                while let Some((new_row, row_edits)) = pending.next_if(|(r, _)| *r < row) {
+                    write_row(&mut writer, row_start(None, new_row)?, new_row, Vec::new(), &row_edits)?;
+                }
+                match pending.next_if(|(r, _)| *r == row) {
+                    Some((_, row_edits)) => {
+                        let start = row_start(Some(e), row)?;
+                        let existing = if matches!(event, Event::Start(_)) {
+                            read_cells(&mut reader)?
+                        } else {
+                            Vec::new()
+                        };
+                        write_row(&mut writer, start, row, existing, &row_edits)?;
+                    }
+                    None => writer.write_event(event).map_err(xml_error)?,
+                }
+            }
+            other => writer.write_event(other).map_err(xml_error)?,
+        }
+    }
+    Ok(writer.into_inner())
+}
+
+/// Drop elements that refer to `xl/calcChain.xml` from a package part.
+fn without_calc_chain(xml: &str) -> Result<Vec<u8>> {
+    let mut reader = Reader::from_str(xml);
+    let mut writer = Writer::new(Vec::new());
+    loop {
+        match reader.read_event().map_err(xml_error)? {
+            Event::Eof => break,
+            Event::Empty(e)
+                if e.attributes().flatten().any(|a| a.value.as_ref().ends_with(b"calcChain.xml")) => {}
+            other => writer.write_event(other).map_err(xml_error)?,
+        }
+    }
+    Ok(writer.into_inner())
+}
+
+/// Write `edits` into `sheet` of an `.xlsx` workbook, returning the new workbook.
+///
+/// Strings are written as inline strings and existing cell styles are kept.
+/// The calculation chain is dropped, since edited cells may have held
+/// formulas; Excel rebuilds it when the workbook is opened.
+pub fn set_cells(workbook: &[u8], sheet: &str, edits: &CellEdits) -> Result<Vec<u8>> {
+    check_not_encrypted(workbook)?;
+    let mut archive = ZipArchive::new(Cursor::new(workbook))
+        .map_err(|_| Error::new_message("only .xlsx and .xlsm workbooks can be written"))?;
+    let sheet_path = sheet_path(&mut archive, sheet)?;
+    let sheet_xml = patch_sheet_xml(&read_entry(&mut archive, &sheet_path)?, edits)?;
+    let has_calc_chain = archive.index_for_name("xl/calcChain.xml").is_some();
+    let mut replaced: BTreeMap<String, Vec<u8>> = BTreeMap::new();
+    replaced.insert(sheet_path, sheet_xml);
+    if has_calc_chain {
+        for part in ["[Content_Types].xml", "xl/_rels/workbook.xml.rels"] {
+            let xml = read_entry(&mut archive, part)?;
+            replaced.insert(part.to_owned(), without_calc_chain(&xml)?);
+        }
+    }
+
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    for i in 0..archive.len() {
+        let file = archive.by_index_raw(i).map_err(zip_error)?;
+        let name = file.name().to_owned();
+        if name == "xl/calcChain.xml" {
+            continue;
+        }
+        match replaced.get(&name) {
+            Some(data) => {
+                drop(file);
+                writer.start_file(name, options).map_err(zip_error)?;
+                writer.write_all(data).map_err(zip_error)?;
+            }
+            None => writer.raw_copy_file(file).map_err(zip_error)?,
+        }
+    }
+    Ok(writer.finish().map_err(zip_error)?.into_inner())
+}
+
+/// Convert an SQL value into a cell value.
+pub fn value_to_data(value: &*mut sqlite3_value) -> Result<Data> {
+    Ok(match api::value_type(value) {
+        api::ValueType::Integer => Data::Int(api::value_int64(value)),
+        api::ValueType::Float => Data::Float(api::value_double(value)),
+        api::ValueType::Text => Data::String(api::value_text(value)?.to_owned()),
+        api::ValueType::Null => Data::Empty,
+        api::ValueType::Blob => return Err(Error::new_message("cannot write a blob into a cell")),
+    })
+}
+
+/// Write values at single-cell references like `Summary!B4`. References
+/// without a sheet name go to the first sheet.
+pub fn set_values(workbook: &[u8], values: &[(&str, Data)]) -> Result<Vec<u8>> {
+    check_not_encrypted(workbook)?;
+    let mut edits: BTreeMap<String, CellEdits> = BTreeMap::new();
+    for (reference, data) in values {
+        let parsed = parse_sheet_reference(reference)
+            .map_err(|e| Error::new_message(format!("invalid cell '{reference}': {e}")))?;
+        let SheetTarget::Cell(cell) = parsed.target else {
+            return Err(Error::new_message(format!("'{reference}' is not a single cell")));
+        };
+        let sheet = match parsed.sheet {
+            Some(sheet) => sheet,
+            None => first_sheet(workbook)?,
+        };
+        let (col, row) = cell.location;
+        edits.entry(sheet).or_default().insert((row, col), data.clone());
+    }
+    let mut workbook = workbook.to_vec();
+    for (sheet, edits) in &edits {
+        workbook = set_cells(&workbook, sheet, edits)?;
+    }
+    Ok(workbook)
+}
+
+fn check_not_encrypted(workbook: &[u8]) -> Result<()> {
+    if crate::workbook::is_encrypted(workbook) {
+        return Err(Error::new_message("cannot write a workbook encrypted with a password"));
+    }
+    Ok(())
+}
+
+fn first_sheet(workbook: &[u8]) -> Result<String> {
+    let workbook = crate::workbook::Workbook::open(workbook.to_vec())
+        .map_err(|e| Error::new_message(format!("cannot open workbook: {e}")))?;
+    workbook
+        .sheet_names()
+        .first()
+        .cloned()
+        .ok_or_else(|| Error::new_message("workbook has no sheets"))
+}
+
+/// `xl_set(workbook, cell, value [, cell, value ...])`: the workbook with
+/// those cells changed.
+pub fn xl_set(context: *mut sqlite3_context, values: &[*mut sqlite3_value]) -> Result<()> {
+    if values.len() < 3 || values.len() % 2 != 1 {
+        return Err(Error::new_message(
+            "xl_set() takes a workbook followed by cell and value pairs",
+        ));
+    }
+    let edits = values[1..]
+        .chunks(2)
+        .map(|pair| Ok((api::value_text(&pair[0])?, value_to_data(&pair[1])?)))
+        .collect::<Result<Vec<_>>>()?;
+    api::result_blob(context, &set_values(api::value_blob(&values[0]), &edits)?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use calamine::Reader;
+
+    fn read(workbook: Vec<u8>, sheet: &str) -> calamine::Range<Data> {
+        let mut workbook = calamine::open_workbook_auto_from_rs(Cursor::new(workbook)).unwrap();
+        workbook.worksheet_range(sheet).unwrap()
+    }
+
+    fn s(v: &str) -> Data {
+        Data::String(v.to_owned())
+    }
+
+    #[test]
+    fn edits_cells() {
+        let workbook = include_bytes!("../tests/report.xlsx");
+        let mut edits = CellEdits::new();
+        // existing cells in existing rows
+        edits.insert((4, 2), Data::Float(11.5));
+        edits.insert((5, 1), s("South & co"));
+        edits.insert((6, 4), Data::Empty);
+        // a new row between existing rows, and one past the end
+        edits.insert((7, 1), s("West"));
+        edits.insert((12, 6), Data::Int(7));
+        let ws = read(set_cells(workbook, "report", &edits).unwrap(), "report");
+
+        assert_eq!(ws.get_value((4, 2)), Some(&Data::Float(11.5)));
+        assert_eq!(ws.get_value((5, 1)), Some(&s("South & co")));
+        assert!(matches!(ws.get_value((6, 4)), None | Some(Data::Empty)));
+        assert_eq!(ws.get_value((7, 1)), Some(&s("West")));
+        assert_eq!(ws.get_value((12, 6)), Some(&Data::Float(7.0)));
+
+        // everything else is untouched
+        assert_eq!(ws.get_value((0, 0)), Some(&s("Quarterly Sales Report")));
+        assert_eq!(ws.get_value((4, 1)), Some(&s("North")));
+        assert_eq!(ws.get_value((4, 3)), Some(&Data::Float(20.0)));
+        assert_eq!(ws.get_value((8, 1)), Some(&s("Note: figures in thousands")));
+    }
+
+    #[test]
+    fn other_sheets_untouched() {
+        let workbook = include_bytes!("../tests/headers.xlsx");
+        let mut edits = CellEdits::new();
+        edits.insert((0, 0), s("Area"));
+        let patched = set_cells(workbook, "stacked", &edits).unwrap();
+        assert_eq!(read(patched.clone(), "stacked").get_value((0, 0)), Some(&s("Area")));
+        assert_eq!(read(patched, "headers").get_value((0, 0)), Some(&s("Region")));
+    }
+
+    #[test]
+    fn errors() {
+        let edits = CellEdits::new();
+        assert!(set_cells(include_bytes!("../tests/report.xlsx"), "nope", &edits).is_err());
+        assert!(set_cells(b"not a workbook", "report", &edits).is_err());
+    }
+
+    #[test]
+    fn values_by_reference() {
+        let workbook = include_bytes!("../tests/headers.xlsx");
+        let patched = set_values(
+            workbook,
+            &[("stacked!A1", s("Area")), ("B2", Data::Int(5)), ("'stacked'!C3", Data::Float(1.5))],
+        )
+        .unwrap();
+        let stacked = read(patched.clone(), "stacked");
+        assert_eq!(stacked.get_value((0, 0)), Some(&s("Area")));
+        assert_eq!(stacked.get_value((2, 2)), Some(&Data::Float(1.5)));
+        assert_eq!(read(patched, "headers").get_value((1, 1)), Some(&Data::Float(5.0)));
+
+        assert!(set_values(workbook, &[("A1:B2", Data::Int(1))]).is_err());
+        assert!(set_values(workbook, &[("stacked!A:B", Data::Int(1))]).is_err());
+        assert!(set_values(workbook, &[("nope!A1", Data::Int(1))]).is_err());
+    }
+
+    #[test]
+    fn shared_formula_master() {
+        let xml = r#"<worksheet><sheetData><row r="1"><c r="A1"><f t="shared" ref="A1:A2" si="0">B1*2</f><v>2</v></c></row><row r="2"><c r="A2"><f t="shared" si="0"/><v>4</v></c></row></sheetData></worksheet>"#;
+        let mut edits = CellEdits::new();
+        edits.insert((0, 0), Data::Int(1));
+        assert_eq!(
+            patch_sheet_xml(xml, &edits).unwrap_err().to_string(),
+            Error::new_message("cannot write A1: other cells share its formula").to_string()
+        );
+
+        // cells that only use a shared formula can be overwritten
+        let mut edits = CellEdits::new();
+        edits.insert((1, 0), Data::Int(1));
+        let patched = String::from_utf8(patch_sheet_xml(xml, &edits).unwrap()).unwrap();
+        assert!(patched.contains(r#"<f t="shared" ref="A1:A2" si="0">B1*2</f>"#));
+        assert!(patched.contains(r#"<c r="A2"><v>1</v></c>"#));
+    }
+
+    #[test]
+    fn non_finite_numbers() {
+        let workbook = include_bytes!("../tests/report.xlsx");
+        for value in [f64::NAN, f64::INFINITY] {
+            let mut edits = CellEdits::new();
+            edits.insert((4, 2), Data::Float(value));
+            assert!(set_cells(workbook, "report", &edits).is_err());
+        }
+    }
+
+    #[test]
+    fn dimension() {
+        let mut edits = CellEdits::new();
+        edits.insert((9, 5), Data::Int(1));
+        assert_eq!(grow_dimension("A1:C4", &edits), "A1:F10");
+        assert_eq!(grow_dimension("B2", &edits), "B2:F10");
+    }
+}
@@ -9,16 +9,19 @@ use sqlite_loadable::{
 use std::ffi::c_void;
 use std::{mem, os::raw::c_int};
 
-use calamine::{Data, Reader};
+use calamine::Data;
 
 use crate::sheet_range::{parse_sheet_reference, SheetTarget};
+use crate::workbook::Workbook;
 
-static CREATE_SQL: &str = "CREATE TABLE x(row_number, row, workbook hidden, sheet hidden)";
+static CREATE_SQL: &str = "CREATE TABLE x(row_number, row, workbook hidden, sheet hidden, stop_at_blank hidden, skip_blank_rows hidden)";
 enum Columns {
     RowNumber,
     Row,
     Workbook,
     Sheet,
+    StopAtBlank,
+    SkipBlankRows,
 }
 fn column(index: i32) -> Option<Columns> {
     match index {
@@ -26,10 +29,17 @@ fn column(index: i32) -> Option<Columns> {
         1 => Some(Columns::Row),
         2 => Some(Columns::Workbook),
         3 => Some(Columns::Sheet),
+        4 => Some(Columns::StopAtBlank),
+        5 => Some(Columns::SkipBlankRows),
         _ => None,
     }
 }
 
+// idx_num flags for the optional arguments passed to filter, in argv order
+const IDX_SHEET: c_int = 0b001;
+const IDX_STOP_AT_BLANK: c_int = 0b010;
+const IDX_SKIP_BLANK_ROWS: c_int = 0b100;
+
 #[repr(C)]
 pub struct RowsTable {
     /// must be first
@@ -56,34 +66,46 @@ impl<'vtab> VTab<'vtab> for RowsTable {
 
     fn best_index(&self, mut info: IndexInfo) -> core::result::Result<(), BestIndexError> {
         let mut has_workbook = false;
-        let mut has_sheet = false;
-        for mut constraint in info.constraints() {
+        let mut idx_num = 0;
+        for constraint in info.constraints() {
+            let usable_eq =
+                constraint.usable() && constraint.op() == Some(ConstraintOperator::EQ);
             match column(constraint.column_idx()) {
                 Some(Columns::Workbook) => {
-                    if constraint.usable() && constraint.op() == Some(ConstraintOperator::EQ) {
-                        constraint.set_omit(true);
-                        constraint.set_argv_index(1);
+                    if usable_eq {
                         has_workbook = true;
                     } else {
                         return Err(BestIndexError::Constraint);
                     }
                 }
-                Some(Columns::Sheet) => {
-                    if constraint.usable() && constraint.op() == Some(ConstraintOperator::EQ) {
-                        constraint.set_omit(true);
-                        constraint.set_argv_index(2);
-                        has_sheet = true;
-                    }
-                }
+                Some(Columns::Sheet) if usable_eq => idx_num |= IDX_SHEET,
+                Some(Columns::StopAtBlank) if usable_eq => idx_num |= IDX_STOP_AT_BLANK,
+                Some(Columns::SkipBlankRows) if usable_eq => idx_num |= IDX_SKIP_BLANK_ROWS,
                 _ => (),
             }
         }
         if !has_workbook {
             return Err(BestIndexError::Error);
         }
+        // optional arguments follow the workbook in a fixed order, skipping absent ones
+        let argv_index = |flag: c_int| 2 + (idx_num & (flag - 1)).count_ones() as i32;
+        for mut constraint in info.constraints() {
+            if !(constraint.usable() && constraint.op() == Some(ConstraintOperator::EQ)) {
+                continue;
+            }
+            let argv = match column(constraint.column_idx()) {
+                Some(Columns::Workbook) => 1,
+                Some(Columns::Sheet) => argv_index(IDX_SHEET),
+                Some(Columns::StopAtBlank) => argv_index(IDX_STOP_AT_BLANK),
+                Some(Columns::SkipBlankRows) => argv_index(IDX_SKIP_BLANK_ROWS),
+                _ => continue,
+            };
+            constraint.set_omit(true);
+            constraint.set_argv_index(argv);
+        }
         info.set_estimated_cost(100000.0);
         info.set_estimated_rows(100000);
-        info.set_idxnum(if has_sheet { 2 } else { 1 });
+        info.set_idxnum(idx_num);
 
         Ok(())
     }
@@ -117,8 +139,8 @@ pub struct RowsCursor {
     /// Base class. Must be first
     base: sqlite3_vtab_cursor,
     rowid: i64,
-    start_row_number: Option<u32>,
-    values: Option<Vec<Vec<Data>>>,
+    /// (1-based row number, row cells)
+    values: Option<Vec<(u32, Vec<Data>)>>,
 }
 impl RowsCursor {
     fn new() -> RowsCursor {
@@ -126,7 +148,6 @@ impl RowsCursor {
         RowsCursor {
             base,
             rowid: 0,
-            start_row_number: None,
             values: None,
         }
     }
@@ -142,46 +163,83 @@ impl VTabCursor for RowsCursor {
         let raw = api::value_blob(values.first().expect("1st min constraint is required"));
         let data = raw.to_vec();
         let mut workbook =
-            calamine::open_workbook_auto_from_rs(std::io::Cursor::new(data))
+            Workbook::open(data)
                 .map_err(|e| sqlite_loadable::Error::new_message(format!("cannot open workbook: {e}")))?;
 
+        let mut args = values.iter().skip(1);
+
         // Parse the optional second argument: can be a plain sheet name,
         // or a sheet-qualified reference like 'Sheet1!A13:*'
-        let (sheet_name, start_row, end_row) = if idx_num == 2 {
-            let arg = api::value_text(values.get(1).unwrap())?;
+        let (sheet_name, start_row, end_row, cols) = if idx_num & IDX_SHEET != 0 {
+            let arg = api::value_text(args.next().unwrap())?;
             match parse_sheet_reference(arg) {
                 Ok(ref parsed) if parsed.sheet.is_some() => {
                     let sheet = parsed.sheet.clone().unwrap();
-                    let (sr, er) = match &parsed.target {
-                        SheetTarget::Range(r) => (Some(r.start.1), Some(r.end.1)),
-                        SheetTarget::OpenRange(r) => (r.start.row, r.end.row),
-                        SheetTarget::Cell(c) => (Some(c.location.1), Some(c.location.1)),
+                    let (sr, er, cols) = match &parsed.target {
+                        SheetTarget::Range(r) => {
+                            (Some(r.start.1), Some(r.end.1), Some((r.start.0, Some(r.end.0))))
+                        }
+                        SheetTarget::OpenRange(r) => {
+                            (r.start.row, r.end.row, Some((r.start.col.unwrap_or(0), r.end.col)))
+                        }
+                        SheetTarget::Cell(c) => (
+                            Some(c.location.1),
+                            Some(c.location.1),
+                            Some((c.location.0, Some(c.location.0))),
+                        ),
                     };
-                    (sheet, sr, er)
+                    (sheet, sr, er, cols)
                 }
                 _ => {
                     // No '!' found or parse failed — treat as plain sheet name
-                    (arg.to_owned(), None, None)
+                    (arg.to_owned(), None, None, None)
                 }
             }
         } else {
-            (workbook.sheet_names().first().unwrap().clone(), None, None)
+            let first = workbook
+                .sheet_names()
+                .first()
+                .cloned()
+                .ok_or_else(|| sqlite_loadable::Error::new_message("workbook has no sheets"))?;
+            (first, None, None, None)
         };
+        let stop_at_blank = idx_num & IDX_STOP_AT_BLANK != 0 && api::value_int64(args.next().unwrap()) != 0;
+        let skip_blank_rows = idx_num & IDX_SKIP_BLANK_ROWS != 0 && api::value_int64(args.next().unwrap()) != 0;
 
         let worksheet_range = workbook.worksheet_range(&sheet_name)
             .map_err(|_| sqlite_loadable::Error::new_message(format!("sheet '{}' not found", sheet_name)))?;
-        let ws_start_row = worksheet_range.start().map(|(_, r)| r).unwrap_or(0);
+        // calamine positions are (row, col)
+        let (ws_start_row, ws_start_col) = worksheet_range.start().unwrap_or((0, 0));
 
         // Apply row bounds from the parsed range
         let skip = start_row.map(|sr| sr.saturating_sub(ws_start_row) as usize).unwrap_or(0);
         let take = end_row.map(|er| (er.saturating_sub(ws_start_row) as usize) + 1 - skip);
 
-        self.start_row_number = Some(ws_start_row + skip as u32 + 1);
-        let iter = worksheet_range.rows().skip(skip);
-        let values: Vec<Vec<Data>> = match take {
-            Some(n) => iter.take(n).map(|v| v.to_owned()).collect(),
-            None => iter.map(|v| v.to_owned()).collect(),
-        };
+        let first_row_number = ws_start_row + skip as u32 + 1;
+        let iter = worksheet_range.rows().skip(skip).take(take.unwrap_or(usize::MAX));
+        let mut values: Vec<(u32, Vec<Data>)> = Vec::new();
+        for (i, row) in iter.enumerate() {
+            if stop_at_blank || skip_blank_rows {
+                // only the columns of the requested range decide whether a row is blank
+                let requested = match cols {
+                    Some((sc, ec)) => {
+                        let sc = (sc.saturating_sub(ws_start_col) as usize).min(row.len());
+                        let ec = ec
+                            .map(|ec| (ec.saturating_sub(ws_start_col) as usize + 1).min(row.len()))
+                            .unwrap_or(row.len());
+                        &row[sc..ec.max(sc)]
+                    }
+                    None => row,
+                };
+                if crate::is_blank_row(requested) {
+                    if stop_at_blank {
+                        break;
+                    }
+                    continue;
+                }
+            }
+            values.push((first_row_number + i as u32, row.to_owned()));
+        }
         self.values = Some(values);
         self.rowid = 0;
         Ok(())
@@ -209,12 +267,15 @@ impl VTabCursor for RowsCursor {
             .unwrap();
         match column(i) {
           Some(Columns::RowNumber) => {
-            api::result_int64(context, self.rowid + self.start_row_number.unwrap()as i64);
+            api::result_int64(context, v.0 as i64);
           }
             Some(Columns::Row) => {
-                api::result_pointer(context, b"ROW\0", v.to_owned());
+                api::result_pointer(context, b"ROW\0", v.1.to_owned());
             }
-            Some(Columns::Workbook) | Some(Columns::Sheet) => {
+            Some(Columns::Workbook)
+            | Some(Columns::Sheet)
+            | Some(Columns::StopAtBlank)
+            | Some(Columns::SkipBlankRows) => {
                 //context_result_int(0);
             }
             None => (),
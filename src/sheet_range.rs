@@ -1,7 +1,11 @@
 use crate::parser::{
-    column_name_to_idx, parse_cell_reference, parse_range_reference, CellReference,
-    RangeReference,
+    column_idx_to_name, column_name_to_idx, parse_cell_reference, parse_range_reference,
+    row_number_to_idx, CellReference, LocationError, ParseCellReferenceError,
+    ParseRangeReferenceError, RangeReference, SheetLimits,
 };
+use serde_json::{json, Value};
+use sqlite_loadable::prelude::*;
+use sqlite_loadable::{api, Error};
 
 /// A parsed sheet-qualified reference like `Sheet1!A1:B10` or `Sheet1!A1`.
 #[derive(Debug, PartialEq)]
@@ -10,6 +14,33 @@ pub struct SheetReference {
     pub target: SheetTarget,
 }
 
+impl SheetReference {
+    /// Check every column and row of the reference against the size of a
+    /// sheet, like the 256 columns and 65,536 rows of an `.xls` file.
+    pub fn check_limits(&self, limits: SheetLimits) -> Result<(), ParseSheetReferenceError> {
+        let corners = match &self.target {
+            SheetTarget::Cell(cell) => vec![(Some(cell.location.0), Some(cell.location.1))],
+            SheetTarget::Range(range) => vec![
+                (Some(range.start.0), Some(range.start.1)),
+                (Some(range.end.0), Some(range.end.1)),
+            ],
+            SheetTarget::OpenRange(range) => vec![
+                (range.start.col, range.start.row),
+                (range.end.col, range.end.row),
+            ],
+        };
+        for (col, row) in corners {
+            if let Some(col) = col {
+                limits.check_column(col)?;
+            }
+            if let Some(row) = row {
+                limits.check_row(row)?;
+            }
+        }
+        Ok(())
+    }
+}
+
 /// A single endpoint in a range that may omit column, row, or neither.
 #[derive(Debug, PartialEq, Clone)]
 pub struct Bound {
@@ -43,6 +74,43 @@ pub enum SheetTarget {
 pub enum ParseSheetReferenceError {
     Empty,
     InvalidReference(String),
+    MultipleAreas,
+    /// Not a column name, like `A1` or `é`
+    InvalidColumn(String),
+    /// Past the last column of the sheet, like `XFE`
+    ColumnOutOfRange { column: String, last: String },
+    /// Row `0`, or past the last row of the sheet
+    RowOutOfRange { row: u64, last: u32 },
+}
+
+impl From<LocationError> for ParseSheetReferenceError {
+    fn from(e: LocationError) -> Self {
+        match e {
+            LocationError::InvalidColumn(column) => Self::InvalidColumn(column),
+            LocationError::ColumnOutOfRange { column, last } => {
+                Self::ColumnOutOfRange { column, last }
+            }
+            LocationError::RowOutOfRange { row, last } => Self::RowOutOfRange { row, last },
+        }
+    }
+}
+
+impl From<ParseCellReferenceError<'_>> for ParseSheetReferenceError {
+    fn from(e: ParseCellReferenceError) -> Self {
+        match e {
+            ParseCellReferenceError::Location(e) => e.into(),
+            e => Self::InvalidReference(e.to_string()),
+        }
+    }
+}
+
+impl From<ParseRangeReferenceError<'_>> for ParseSheetReferenceError {
+    fn from(e: ParseRangeReferenceError) -> Self {
+        match e {
+            ParseRangeReferenceError::Location(e) => e.into(),
+            e => Self::InvalidReference(e.to_string()),
+        }
+    }
 }
 
 impl std::fmt::Display for ParseSheetReferenceError {
@@ -50,6 +118,12 @@ impl std::fmt::Display for ParseSheetReferenceError {
         match self {
             Self::Empty => write!(f, "empty input"),
             Self::InvalidReference(msg) => write!(f, "invalid reference: {msg}"),
+            Self::MultipleAreas => write!(f, "expected a single area, not a list of areas"),
+            Self::InvalidColumn(column) => write!(f, "invalid column name '{column}'"),
+            Self::ColumnOutOfRange { column, last } => {
+                write!(f, "column {column} is past the last column {last}")
+            }
+            Self::RowOutOfRange { row, last } => write!(f, "row {row} is outside rows 1 to {last}"),
         }
     }
 }
@@ -66,12 +140,18 @@ impl std::fmt::Display for ParseSheetReferenceError {
 /// - `Sheet1!A1`     → cell reference with sheet
 /// - `Sheet1!A1:B10` → range with sheet
 /// - `'My Sheet'!A:B`→ quoted sheet name
+/// - `'Bob''s'!A1`  → quoted sheet name with an escaped `'`
+/// - `R2C1:R100C6`   → R1C1 notation, when the input isn't a valid A1 reference
 pub fn parse_sheet_reference(input: &str) -> Result<SheetReference, ParseSheetReferenceError> {
     let input = input.trim();
     if input.is_empty() {
         return Err(ParseSheetReferenceError::Empty);
     }
 
+    if split_areas(input).len() > 1 {
+        return Err(ParseSheetReferenceError::MultipleAreas);
+    }
+
     let (sheet, ref_part) = split_sheet_and_ref(input);
 
     let target = parse_target(ref_part)?;
@@ -79,17 +159,65 @@ pub fn parse_sheet_reference(input: &str) -> Result<SheetReference, ParseSheetRe
     Ok(SheetReference { sheet, target })
 }
 
+/// Parse a comma-separated list of areas like `A1:B5,D1:D5` or
+/// `Sheet1!A1:B2,Sheet2!C3`, as in Excel selections and defined names.
+///
+/// An area without a sheet name is on the same sheet as the area before it.
+pub fn parse_sheet_areas(input: &str) -> Result<Vec<SheetReference>, ParseSheetReferenceError> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Err(ParseSheetReferenceError::Empty);
+    }
+
+    let mut areas: Vec<SheetReference> = Vec::new();
+    for area in split_areas(input) {
+        let mut reference = parse_sheet_reference(area)?;
+        if reference.sheet.is_none() {
+            reference.sheet = areas.last().and_then(|prev| prev.sheet.clone());
+        }
+        areas.push(reference);
+    }
+    Ok(areas)
+}
+
+/// Split `A1:B5,'Q1, Q2'!D1` on the commas outside quoted sheet names.
+fn split_areas(input: &str) -> Vec<&str> {
+    let mut areas = Vec::new();
+    let mut quoted = false;
+    let mut start = 0;
+    for (i, c) in input.char_indices() {
+        match c {
+            '\'' => quoted = !quoted,
+            ',' if !quoted => {
+                areas.push(&input[start..i]);
+                start = i + 1;
+            }
+            _ => (),
+        }
+    }
+    areas.push(&input[start..]);
+    areas
+}
+
 /// Split `Sheet1!A1:B10` into `(Some("Sheet1"), "A1:B10")`.
 /// Split `'My Sheet'!A1` into `(Some("My Sheet"), "A1")`.
+/// Split `'Bob''s Data'!A1` into `(Some("Bob's Data"), "A1")`.
 /// Split `A1:B10` into `(None, "A1:B10")`.
 fn split_sheet_and_ref(input: &str) -> (Option<String>, &str) {
-    if input.starts_with('\'') {
-        // Quoted sheet name: find closing quote then '!'
-        if let Some(end_quote) = input[1..].find('\'') {
-            let sheet_name = &input[1..=end_quote];
-            let rest = &input[end_quote + 2..]; // skip closing quote
-            if let Some(ref_part) = rest.strip_prefix('!') {
-                return (Some(sheet_name.to_owned()), ref_part);
+    if let Some(quoted) = input.strip_prefix('\'') {
+        // Quoted sheet name: `''` is an escaped quote, a single `'` closes it
+        let mut sheet_name = String::new();
+        let mut chars = quoted.char_indices().peekable();
+        while let Some((i, c)) = chars.next() {
+            if c != '\'' {
+                sheet_name.push(c);
+            } else if chars.next_if(|(_, c)| *c == '\'').is_some() {
+                sheet_name.push('\'');
+            } else {
+                if let Some(ref_part) = quoted[i + 1..].strip_prefix('!') {
+                    return (Some(sheet_name), ref_part);
+                }
+                break;
             }
         }
         // Malformed quote — treat entire input as reference
@@ -141,20 +269,17 @@ fn parse_bound(s: &str) -> Result<Bound, ParseSheetReferenceError> {
                 .ok_or_else(|| ParseSheetReferenceError::InvalidReference(s.to_owned()))?;
             let col_part = &s[..split];
             let row_part = &s[split..];
-            let col = column_name_to_idx(col_part)
-                .map_err(|_| ParseSheetReferenceError::InvalidReference(s.to_owned()))?;
-            let row: u32 = row_part
-                .parse()
-                .map_err(|_| ParseSheetReferenceError::InvalidReference(s.to_owned()))?;
+            let col = column_name_to_idx(col_part)?;
+            let row = parse_row_number(row_part)
+                .ok_or_else(|| ParseSheetReferenceError::InvalidReference(s.to_owned()))?;
             Ok(Bound {
                 col: Some(col),
-                row: if row_wildcard { None } else { Some(row - 1) },
+                row: if row_wildcard { None } else { Some(row_number_to_idx(row)?) },
             })
         }
         (true, false) => {
             // Column-only like `A`, `D`, `ZZ`
-            let col = column_name_to_idx(s)
-                .map_err(|_| ParseSheetReferenceError::InvalidReference(s.to_owned()))?;
+            let col = column_name_to_idx(s)?;
             Ok(Bound {
                 col: Some(col),
                 row: None,
@@ -162,18 +287,26 @@ fn parse_bound(s: &str) -> Result<Bound, ParseSheetReferenceError> {
         }
         (false, true) => {
             // Row-only like `1`, `10`, `9999`
-            let row: u32 = s
-                .parse()
-                .map_err(|_| ParseSheetReferenceError::InvalidReference(s.to_owned()))?;
+            let row = parse_row_number(s)
+                .ok_or_else(|| ParseSheetReferenceError::InvalidReference(s.to_owned()))?;
             Ok(Bound {
                 col: None,
-                row: Some(row - 1),
+                row: Some(row_number_to_idx(row)?),
             })
         }
         (false, false) => Err(ParseSheetReferenceError::InvalidReference(s.to_owned())),
     }
 }
 
+/// Parse the digits of a 1-based row number. Numbers too large for any row
+/// saturate, so they are reported as out of range rather than invalid.
+fn parse_row_number(s: &str) -> Option<u64> {
+    if s.is_empty() || !s.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    Some(s.parse().unwrap_or(u64::MAX))
+}
+
 /// Determine if a range has any partial bounds (col-only, row-only, or wildcard).
 fn has_partial_bound(left: &str, right: &str) -> bool {
     if left.contains('*') || right.contains('*') {
@@ -188,11 +321,64 @@ fn has_partial_bound(left: &str, right: &str) -> bool {
     !(left_alpha && left_digit && right_alpha && right_digit)
 }
 
-/// Try to parse as range first (contains ':'), then as cell.
+/// Parse an A1 reference, or an R1C1 reference like `R2C1` or `R5C` that
+/// can't be read as A1. References valid in both, like `C3:C5`, keep their
+/// A1 meaning.
 fn parse_target(input: &str) -> Result<SheetTarget, ParseSheetReferenceError> {
     // Strip optional $ signs (absolute references)
     let cleaned: String = input.chars().filter(|c| *c != '$').collect();
 
+    let target = if is_r1c1(&cleaned) {
+        parse_r1c1_target(&cleaned)?
+    } else {
+        parse_a1_target(&cleaned)?
+    };
+    Ok(normalize(target))
+}
+
+/// Order the corners of a reversed range like `B5:A1` as `A1:B5`.
+fn normalize(target: SheetTarget) -> SheetTarget {
+    fn order(a: Option<u32>, b: Option<u32>) -> (Option<u32>, Option<u32>) {
+        match (a, b) {
+            (Some(a), Some(b)) if a > b => (Some(b), Some(a)),
+            _ => (a, b),
+        }
+    }
+    match target {
+        SheetTarget::Range(RangeReference { start, end }) => SheetTarget::Range(RangeReference {
+            start: (start.0.min(end.0), start.1.min(end.1)),
+            end: (start.0.max(end.0), start.1.max(end.1)),
+        }),
+        SheetTarget::OpenRange(OpenRange { start, end }) => {
+            let (start_col, end_col) = order(start.col, end.col);
+            let (start_row, end_row) = order(start.row, end.row);
+            SheetTarget::OpenRange(OpenRange {
+                start: Bound {
+                    col: start_col,
+                    row: start_row,
+                },
+                end: Bound {
+                    col: end_col,
+                    row: end_row,
+                },
+            })
+        }
+        cell => cell,
+    }
+}
+
+/// Whether a reference is written in R1C1 notation: it starts with `R` or `C`
+/// and has a bound that isn't shaped like A1 (letters, then digits).
+fn is_r1c1(input: &str) -> bool {
+    let a1_shaped = |bound: &str| {
+        let digits = bound.trim_start_matches(|c: char| c.is_ascii_alphabetic());
+        digits.trim_end_matches('*').chars().all(|c| c.is_ascii_digit())
+    };
+    input.starts_with(['R', 'r', 'C', 'c']) && !input.split(':').all(a1_shaped)
+}
+
+/// Try to parse as range first (contains ':'), then as cell.
+fn parse_a1_target(cleaned: &str) -> Result<SheetTarget, ParseSheetReferenceError> {
     if let Some((left, right)) = cleaned.split_once(':') {
         if has_partial_bound(left, right) {
             // At least one side is unbounded — use OpenRange
@@ -201,17 +387,459 @@ fn parse_target(input: &str) -> Result<SheetTarget, ParseSheetReferenceError> {
             Ok(SheetTarget::OpenRange(OpenRange { start, end }))
         } else {
             // Both sides are full cells — use the existing bounded parser
-            parse_range_reference(&cleaned)
-                .map(SheetTarget::Range)
-                .map_err(|e| ParseSheetReferenceError::InvalidReference(e.to_string()))
+            Ok(SheetTarget::Range(parse_range_reference(cleaned)?))
+        }
+    } else {
+        Ok(SheetTarget::Cell(parse_cell_reference(cleaned)?))
+    }
+}
+
+/// Parse a sheet-qualified reference in R1C1 notation only.
+///
+/// Supported formats, with 1-based absolute row and column numbers:
+/// - `R2C1`          → cell reference
+/// - `R2C1:R100C6`   → bounded range
+/// - `R5C`, `R5`     → whole row 5
+/// - `RC3`, `C3`     → whole column 3
+/// - `R2C1:C6`       → mixed, like `A2:F` in A1 notation
+/// - `Sheet1!R1C1`   → with a sheet name, quoted or not
+///
+/// Relative references like `R[-1]C` have no meaning outside a formula and
+/// are rejected.
+pub fn parse_r1c1_reference(input: &str) -> Result<SheetReference, ParseSheetReferenceError> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Err(ParseSheetReferenceError::Empty);
+    }
+
+    let (sheet, ref_part) = split_sheet_and_ref(input);
+
+    let target = normalize(parse_r1c1_target(ref_part)?);
+
+    Ok(SheetReference { sheet, target })
+}
+
+/// Parse an R1C1 target like `R2C1`, `R5C` or `R2C1:R100C6`.
+fn parse_r1c1_target(input: &str) -> Result<SheetTarget, ParseSheetReferenceError> {
+    if let Some((left, right)) = input.split_once(':') {
+        let start = parse_r1c1_bound(left)?;
+        let end = parse_r1c1_bound(right)?;
+        match (start, end) {
+            (
+                Bound {
+                    col: Some(c1),
+                    row: Some(r1),
+                },
+                Bound {
+                    col: Some(c2),
+                    row: Some(r2),
+                },
+            ) => Ok(SheetTarget::Range(RangeReference {
+                start: (c1, r1),
+                end: (c2, r2),
+            })),
+            (start, end) => Ok(SheetTarget::OpenRange(OpenRange { start, end })),
+        }
+    } else {
+        match parse_r1c1_bound(input)? {
+            Bound {
+                col: Some(col),
+                row: Some(row),
+            } => Ok(SheetTarget::Cell(CellReference {
+                location: (col, row),
+            })),
+            // A whole row or column on its own, like `R5C` or `C3`
+            bound => Ok(SheetTarget::OpenRange(OpenRange {
+                start: bound.clone(),
+                end: bound,
+            })),
+        }
+    }
+}
+
+/// Parse a single R1C1 bound like `R2C1`, `R5C`, `R5`, `RC3` or `C3`.
+fn parse_r1c1_bound(s: &str) -> Result<Bound, ParseSheetReferenceError> {
+    let invalid = || ParseSheetReferenceError::InvalidReference(s.to_owned());
+    if s.contains('[') {
+        return Err(ParseSheetReferenceError::InvalidReference(format!(
+            "relative R1C1 references are not supported: {s}"
+        )));
+    }
+
+    let upper = s.to_ascii_uppercase();
+    let (row_part, col_part) = match upper.strip_prefix('R') {
+        Some(rest) => match rest.split_once('C') {
+            Some((row, col)) => (Some(row), Some(col)),
+            None => (Some(rest), None),
+        },
+        None => (None, Some(upper.strip_prefix('C').ok_or_else(invalid)?)),
+    };
+
+    // `R` or `C` without a number means every row or column
+    let number = |part: Option<&str>| -> Result<Option<u64>, ParseSheetReferenceError> {
+        match part {
+            None | Some("") => Ok(None),
+            Some(digits) => parse_row_number(digits).map(Some).ok_or_else(invalid),
+        }
+    };
+    let row = number(row_part)?.map(row_number_to_idx).transpose()?;
+    let col = match number(col_part)? {
+        Some(n) => {
+            let idx = u32::try_from(n).ok().and_then(|n| n.checked_sub(1)).ok_or_else(invalid)?;
+            SheetLimits::XLSX.check_column(idx)?;
+            Some(idx)
+        }
+        None => None,
+    };
+    if row.is_none() && col.is_none() {
+        return Err(invalid());
+    }
+    Ok(Bound { col, row })
+}
+
+/// Format a parsed target in A1 notation, like `A1`, `A1:B5`, `A:B` or `5:5`.
+pub fn format_a1_target(target: &SheetTarget) -> String {
+    fn bound(b: &Bound) -> String {
+        let col = b.col.map(column_idx_to_name).unwrap_or_default();
+        let row = b.row.map(|r| (r + 1).to_string()).unwrap_or_default();
+        match (b.col, b.row) {
+            (None, None) => "*".to_owned(),
+            _ => format!("{col}{row}"),
+        }
+    }
+    match target {
+        SheetTarget::Cell(cell) => {
+            let (col, row) = cell.location;
+            format!("{}{}", column_idx_to_name(col), row + 1)
         }
+        SheetTarget::Range(range) => format!(
+            "{}{}:{}{}",
+            column_idx_to_name(range.start.0),
+            range.start.1 + 1,
+            column_idx_to_name(range.end.0),
+            range.end.1 + 1
+        ),
+        SheetTarget::OpenRange(open) => format!("{}:{}", bound(&open.start), bound(&open.end)),
+    }
+}
+
+/// Quote a sheet name for use in a reference, like `'My Sheet'` or
+/// `'Bob''s Data'`, when Excel would. Names of only letters, digits, `_` and
+/// `.` are left as they are, unless they start with a digit or could be read
+/// as a reference themselves, like `Q1`, `R2C3` or `C`.
+pub fn quote_sheet_name(sheet: &str) -> String {
+    if needs_quotes(sheet) {
+        format!("'{}'", sheet.replace('\'', "''"))
     } else {
-        parse_cell_reference(&cleaned)
-            .map(SheetTarget::Cell)
-            .map_err(|e| ParseSheetReferenceError::InvalidReference(e.to_string()))
+        sheet.to_owned()
+    }
+}
+
+fn needs_quotes(sheet: &str) -> bool {
+    let looks_like_a1 = || {
+        let digits = sheet.trim_start_matches(|c: char| c.is_ascii_alphabetic());
+        !digits.is_empty()
+            && digits.chars().all(|c| c.is_ascii_digit())
+            && column_name_to_idx(&sheet[..sheet.len() - digits.len()]).is_ok()
+    };
+    let looks_like_r1c1 = || {
+        sheet.eq_ignore_ascii_case("R")
+            || sheet.eq_ignore_ascii_case("C")
+            || parse_r1c1_bound(sheet).is_ok()
+    };
+    sheet.is_empty()
+        || !sheet.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '.')
+        || sheet.starts_with(|c: char| c.is_ascii_digit())
+        || looks_like_a1()
+        || looks_like_r1c1()
+}
+
+/// `xl_quote_sheet(name)`: quote a sheet name, like one from `xl_sheets()`,
+/// to build a reference such as `'Bob''s Data'!A1`.
+pub fn xl_quote_sheet(context: *mut sqlite3_context, values: &[*mut sqlite3_value]) -> sqlite_loadable::Result<()> {
+    let sheet = api::value_text(&values[0])?;
+    api::result_text(context, quote_sheet_name(sheet))?;
+    Ok(())
+}
+
+/// A rectangle of cells, with 0-based inclusive `(column, row)` corners.
+/// Unbounded sides of a range extend to the last column or row of an
+/// `.xlsx` sheet.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Area {
+    pub start: (u32, u32),
+    pub end: (u32, u32),
+}
+
+impl Area {
+    pub fn from_target(target: &SheetTarget) -> Area {
+        let last = (SheetLimits::XLSX.max_columns - 1, SheetLimits::XLSX.max_rows - 1);
+        match target {
+            SheetTarget::Cell(cell) => Area {
+                start: cell.location,
+                end: cell.location,
+            },
+            SheetTarget::Range(range) => Area {
+                start: range.start,
+                end: range.end,
+            },
+            SheetTarget::OpenRange(range) => Area {
+                start: (range.start.col.unwrap_or(0), range.start.row.unwrap_or(0)),
+                end: (range.end.col.unwrap_or(last.0), range.end.row.unwrap_or(last.1)),
+            },
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.end.0 - self.start.0 + 1
+    }
+
+    pub fn height(&self) -> u32 {
+        self.end.1 - self.start.1 + 1
+    }
+
+    /// Whether every cell of `other` is in this area.
+    pub fn contains(&self, other: &Area) -> bool {
+        self.start.0 <= other.start.0
+            && self.start.1 <= other.start.1
+            && other.end.0 <= self.end.0
+            && other.end.1 <= self.end.1
+    }
+
+    /// The cells in both areas, if any.
+    pub fn intersect(&self, other: &Area) -> Option<Area> {
+        let start = (self.start.0.max(other.start.0), self.start.1.max(other.start.1));
+        let end = (self.end.0.min(other.end.0), self.end.1.min(other.end.1));
+        (start.0 <= end.0 && start.1 <= end.1).then_some(Area { start, end })
+    }
+
+    /// Move the area by `rows` and `cols` and resize it to `height` by
+    /// `width`, like Excel's `OFFSET()`. `None` when it leaves the sheet.
+    pub fn offset(&self, rows: i64, cols: i64, height: u32, width: u32) -> Option<Area> {
+        let limits = SheetLimits::XLSX;
+        let moved = |from: u32, by: i64, size: u32, max: u32| -> Option<(u32, u32)> {
+            let start = u32::try_from(i64::from(from).checked_add(by)?).ok()?;
+            let end = start.checked_add(size.checked_sub(1)?)?;
+            (end < max).then_some((start, end))
+        };
+        let (start_col, end_col) = moved(self.start.0, cols, width, limits.max_columns)?;
+        let (start_row, end_row) = moved(self.start.1, rows, height, limits.max_rows)?;
+        Some(Area {
+            start: (start_col, start_row),
+            end: (end_col, end_row),
+        })
+    }
+
+    /// Format as A1 notation: `B3` for a single cell, `A:B` for whole
+    /// columns, `1:5` for whole rows, or `A1:B5`.
+    pub fn to_a1(self) -> String {
+        let last = (SheetLimits::XLSX.max_columns - 1, SheetLimits::XLSX.max_rows - 1);
+        let whole_columns = self.start.1 == 0 && self.end.1 == last.1;
+        let whole_rows = self.start.0 == 0 && self.end.0 == last.0;
+        if whole_columns && !whole_rows {
+            format!("{}:{}", column_idx_to_name(self.start.0), column_idx_to_name(self.end.0))
+        } else if whole_rows && !whole_columns {
+            format!("{}:{}", self.start.1 + 1, self.end.1 + 1)
+        } else if self.start == self.end {
+            format_address(self.start.0, self.start.1, None, false)
+        } else {
+            format!(
+                "{}:{}",
+                format_address(self.start.0, self.start.1, None, false),
+                format_address(self.end.0, self.end.1, None, false)
+            )
+        }
+    }
+}
+
+/// An area in A1 notation, with its sheet name quoted when there is one.
+fn format_area(sheet: Option<&str>, area: &Area) -> String {
+    match sheet {
+        Some(sheet) => format!("{}!{}", quote_sheet_name(sheet), area.to_a1()),
+        None => area.to_a1(),
+    }
+}
+
+/// Parse a reference argument of the range functions.
+fn reference_arg(value: &*mut sqlite3_value) -> sqlite_loadable::Result<(Option<String>, Area)> {
+    let input = api::value_text(value)?;
+    let reference = parse_sheet_reference(input)
+        .map_err(|e| Error::new_message(format!("invalid range '{input}': {e}")))?;
+    Ok((reference.sheet, Area::from_target(&reference.target)))
+}
+
+/// Whether two references can be on the same sheet: they name the same
+/// sheet, or at least one of them has no sheet name.
+fn same_sheet(a: &Option<String>, b: &Option<String>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => a == b,
+        _ => true,
+    }
+}
+
+/// `xl_range_contains(range, reference)`: `1` if every cell of `reference`
+/// is in `range`, `0` otherwise.
+pub fn xl_range_contains(context: *mut sqlite3_context, values: &[*mut sqlite3_value]) -> sqlite_loadable::Result<()> {
+    let (sheet, range) = reference_arg(&values[0])?;
+    let (other_sheet, other) = reference_arg(&values[1])?;
+    let contains = same_sheet(&sheet, &other_sheet) && range.contains(&other);
+    api::result_bool(context, contains);
+    Ok(())
+}
+
+/// `xl_range_intersect(a, b)`: the cells in both references, or `NULL`.
+pub fn xl_range_intersect(context: *mut sqlite3_context, values: &[*mut sqlite3_value]) -> sqlite_loadable::Result<()> {
+    let (sheet, a) = reference_arg(&values[0])?;
+    let (other_sheet, b) = reference_arg(&values[1])?;
+    match a.intersect(&b).filter(|_| same_sheet(&sheet, &other_sheet)) {
+        Some(area) => api::result_text(context, format_area(sheet.or(other_sheet).as_deref(), &area))?,
+        None => api::result_null(context),
+    }
+    Ok(())
+}
+
+/// `xl_offset(reference, rows, cols [, height, width])`: the reference moved
+/// by `rows` and `cols`, and resized when `height` or `width` are given.
+pub fn xl_offset(context: *mut sqlite3_context, values: &[*mut sqlite3_value]) -> sqlite_loadable::Result<()> {
+    let (sheet, area) = reference_arg(&values[0])?;
+    let rows = api::value_int64(&values[1]);
+    let cols = api::value_int64(&values[2]);
+    let size = |idx: usize, what: &str, default: u32| -> sqlite_loadable::Result<u32> {
+        match values.get(idx) {
+            Some(value) if !matches!(api::value_type(value), api::ValueType::Null) => {
+                let size = api::value_int64(value);
+                u32::try_from(size).ok().filter(|size| *size >= 1).ok_or_else(|| {
+                    Error::new_message(format!("{what} must be at least 1, got {size}"))
+                })
+            }
+            _ => Ok(default),
+        }
+    };
+    let height = size(3, "height", area.height())?;
+    let width = size(4, "width", area.width())?;
+    let moved = area
+        .offset(rows, cols, height, width)
+        .ok_or_else(|| Error::new_message("offset range is outside the sheet"))?;
+    api::result_text(context, format_area(sheet.as_deref(), &moved))?;
+    Ok(())
+}
+
+/// `xl_range_size(reference)`: the number of `rows`, `columns` and `cells`
+/// of a reference, as JSON.
+pub fn xl_range_size(context: *mut sqlite3_context, values: &[*mut sqlite3_value]) -> sqlite_loadable::Result<()> {
+    let (_, area) = reference_arg(&values[0])?;
+    api::result_json(
+        context,
+        json!({
+            "rows": area.height(),
+            "columns": area.width(),
+            "cells": u64::from(area.height()) * u64::from(area.width()),
+        }),
+    )?;
+    Ok(())
+}
+
+/// Format a 0-based cell location as an address like `B3`, `$B$3` or
+/// `'My Sheet'!B3`.
+pub fn format_address(col: u32, row: u32, sheet: Option<&str>, absolute: bool) -> String {
+    let dollar = if absolute { "$" } else { "" };
+    let address = format!("{dollar}{}{dollar}{}", column_idx_to_name(col), row + 1);
+    match sheet {
+        Some(sheet) => format!("{}!{address}", quote_sheet_name(sheet)),
+        None => address,
     }
 }
 
+/// A 0-based column or row index argument, within the limits of an `.xlsx` sheet.
+fn index_arg(value: &*mut sqlite3_value, what: &str, max: u32) -> sqlite_loadable::Result<u32> {
+    if !matches!(api::value_type(value), api::ValueType::Integer) {
+        return Err(Error::new_message(format!("{what} index must be an integer")));
+    }
+    let idx = api::value_int64(value);
+    u32::try_from(idx)
+        .ok()
+        .filter(|idx| *idx < max)
+        .ok_or_else(|| {
+            Error::new_message(format!("{what} index {idx} is outside 0 to {}", max - 1))
+        })
+}
+
+/// `xl_column_name(idx)`: the name of a 0-based column index, like `0` → `A`.
+pub fn xl_column_name(context: *mut sqlite3_context, values: &[*mut sqlite3_value]) -> sqlite_loadable::Result<()> {
+    let col = index_arg(&values[0], "column", SheetLimits::XLSX.max_columns)?;
+    api::result_text(context, column_idx_to_name(col))?;
+    Ok(())
+}
+
+/// `xl_column_index(name)`: the 0-based index of a column name, like `AA` → `26`.
+pub fn xl_column_index(context: *mut sqlite3_context, values: &[*mut sqlite3_value]) -> sqlite_loadable::Result<()> {
+    let name = api::value_text(&values[0])?;
+    let col = column_name_to_idx(name.trim().trim_start_matches('$'))
+        .map_err(|e| Error::new_message(e.to_string()))?;
+    api::result_int64(context, col.into());
+    Ok(())
+}
+
+/// `xl_address(column_idx, row_idx [, sheet, absolute])`: the address of a
+/// 0-based cell location, like `xl_address(1, 2)` → `B3`.
+pub fn xl_address(context: *mut sqlite3_context, values: &[*mut sqlite3_value]) -> sqlite_loadable::Result<()> {
+    let col = index_arg(&values[0], "column", SheetLimits::XLSX.max_columns)?;
+    let row = index_arg(&values[1], "row", SheetLimits::XLSX.max_rows)?;
+    let sheet = match values.get(2) {
+        Some(value) if !matches!(api::value_type(value), api::ValueType::Null) => Some(api::value_text(value)?),
+        _ => None,
+    };
+    let absolute = values.get(3).is_some_and(|value| api::value_int64(value) != 0);
+    api::result_text(context, format_address(col, row, sheet, absolute))?;
+    Ok(())
+}
+
+/// A parsed reference as JSON, with 0-based `column` and `row` indexes for
+/// its start and end, `null` where the range is unbounded.
+pub fn reference_json(reference: &SheetReference) -> Value {
+    let (start, end) = match &reference.target {
+        SheetTarget::Cell(cell) => ((Some(cell.location.0), Some(cell.location.1)), (Some(cell.location.0), Some(cell.location.1))),
+        SheetTarget::Range(range) => (
+            (Some(range.start.0), Some(range.start.1)),
+            (Some(range.end.0), Some(range.end.1)),
+        ),
+        SheetTarget::OpenRange(range) => (
+            (range.start.col, range.start.row),
+            (range.end.col, range.end.row),
+        ),
+    };
+    json!({
+        "sheet": reference.sheet,
+        "start": {"column": start.0, "row": start.1},
+        "end": {"column": end.0, "row": end.1},
+        "address": format_a1_target(&reference.target),
+    })
+}
+
+/// `xl_parse_range(reference)`: a reference like `Sheet1!A1:B5` as JSON.
+pub fn xl_parse_range(context: *mut sqlite3_context, values: &[*mut sqlite3_value]) -> sqlite_loadable::Result<()> {
+    let input = api::value_text(&values[0])?;
+    let reference = parse_sheet_reference(input)
+        .map_err(|e| Error::new_message(format!("invalid range '{input}': {e}")))?;
+    api::result_json(context, reference_json(&reference))?;
+    Ok(())
+}
+
+/// `xl_r1c1_to_a1(reference)`: convert an R1C1 reference like `R2C1:R100C6`
+/// to A1 notation like `A2:F100`, keeping any sheet name.
+pub fn xl_r1c1_to_a1(context: *mut sqlite3_context, values: &[*mut sqlite3_value]) -> sqlite_loadable::Result<()> {
+    let input = api::value_text(&values[0])?;
+    let reference = parse_r1c1_reference(input)
+        .map_err(|e| Error::new_message(format!("invalid R1C1 reference '{input}': {e}")))?;
+    let target = format_a1_target(&reference.target);
+    let a1 = match reference.sheet {
+        Some(sheet) => format!("{}!{target}", quote_sheet_name(&sheet)),
+        None => target,
+    };
+    api::result_text(context, a1)?;
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -371,6 +999,82 @@ mod tests {
         );
     }
 
+    #[test]
+    fn quoted_sheet_escaped_quote() {
+        assert_eq!(
+            parse_sheet_reference("'Bob''s Data'!A1"),
+            Ok(sr(Some("Bob's Data"), cell(0, 0)))
+        );
+        assert_eq!(
+            parse_sheet_reference("'''Quoted'''!B2:C3"),
+            Ok(sr(Some("'Quoted'"), range(1, 1, 2, 2)))
+        );
+    }
+
+    #[test]
+    fn quoted_sheet_with_bang() {
+        assert_eq!(
+            parse_sheet_reference("'Hello!World'!A1:B2"),
+            Ok(sr(Some("Hello!World"), range(0, 0, 1, 1)))
+        );
+    }
+
+    #[test]
+    fn quoted_sheet_with_comma_area() {
+        assert_eq!(
+            parse_sheet_areas("'It''s, here'!A1,B2"),
+            Ok(vec![
+                sr(Some("It's, here"), cell(0, 0)),
+                sr(Some("It's, here"), cell(1, 1)),
+            ])
+        );
+    }
+
+    #[test]
+    fn unicode_sheet_names() {
+        assert_eq!(
+            parse_sheet_reference("Données!A1"),
+            Ok(sr(Some("Données"), cell(0, 0)))
+        );
+        assert_eq!(
+            parse_sheet_reference("'売上 2024'!B2"),
+            Ok(sr(Some("売上 2024"), cell(1, 1)))
+        );
+    }
+
+    #[test]
+    fn quoted_sheet_malformed() {
+        assert!(parse_sheet_reference("'Unclosed!A1").is_err());
+        assert!(parse_sheet_reference("'Bob''s'A1").is_err());
+    }
+
+    #[test]
+    fn quote_sheet_names() {
+        assert_eq!(quote_sheet_name("Sheet1"), "Sheet1");
+        assert_eq!(quote_sheet_name("my_data.v2"), "my_data.v2");
+        assert_eq!(quote_sheet_name("Données"), "Données");
+        assert_eq!(quote_sheet_name("My Sheet"), "'My Sheet'");
+        assert_eq!(quote_sheet_name("Bob's Data"), "'Bob''s Data'");
+        assert_eq!(quote_sheet_name("Hello!World"), "'Hello!World'");
+        assert_eq!(quote_sheet_name("2024"), "'2024'");
+        assert_eq!(quote_sheet_name("Q1"), "'Q1'");
+        assert_eq!(quote_sheet_name("R2C3"), "'R2C3'");
+        assert_eq!(quote_sheet_name("c"), "'c'");
+        assert_eq!(quote_sheet_name(""), "''");
+    }
+
+    #[test]
+    fn quote_sheet_round_trip() {
+        for name in ["Sheet1", "Bob's Data", "'Quoted'", "a!b, c", "Q1", "売上 2024", "''"] {
+            let reference = format!("{}!A1", quote_sheet_name(name));
+            assert_eq!(
+                parse_sheet_reference(&reference),
+                Ok(sr(Some(name), cell(0, 0))),
+                "{reference}"
+            );
+        }
+    }
+
     // ═══════════════════════════════════════════
     //  Absolute references ($)
     // ═══════════════════════════════════════════
@@ -698,4 +1402,433 @@ mod tests {
     fn colon_left_empty() {
         assert!(parse_sheet_reference(":B").is_err());
     }
+
+    // ═══════════════════════════════════════════
+    //  Excel limits and reversed ranges
+    // ═══════════════════════════════════════════
+
+    #[test]
+    fn last_cell() {
+        assert_eq!(
+            parse_sheet_reference("A1:XFD1048576"),
+            Ok(sr(None, range(0, 0, 16383, 1048575)))
+        );
+    }
+
+    #[test]
+    fn column_past_xfd() {
+        assert_eq!(
+            parse_sheet_reference("A1:XFE2"),
+            Err(ParseSheetReferenceError::ColumnOutOfRange {
+                column: "XFE".to_owned(),
+                last: "XFD".to_owned()
+            })
+        );
+        assert!(matches!(
+            parse_sheet_reference("A:ZZZZZZZZ"),
+            Err(ParseSheetReferenceError::ColumnOutOfRange { .. })
+        ));
+    }
+
+    #[test]
+    fn row_zero() {
+        assert_eq!(
+            parse_sheet_reference("A0"),
+            Err(ParseSheetReferenceError::RowOutOfRange {
+                row: 0,
+                last: 1048576
+            })
+        );
+        assert_eq!(
+            parse_sheet_reference("0:5"),
+            Err(ParseSheetReferenceError::RowOutOfRange {
+                row: 0,
+                last: 1048576
+            })
+        );
+        assert_eq!(
+            parse_sheet_reference("A0:D"),
+            Err(ParseSheetReferenceError::RowOutOfRange {
+                row: 0,
+                last: 1048576
+            })
+        );
+    }
+
+    #[test]
+    fn row_past_limit() {
+        assert_eq!(
+            parse_sheet_reference("1:1048577"),
+            Err(ParseSheetReferenceError::RowOutOfRange {
+                row: 1048577,
+                last: 1048576
+            })
+        );
+        assert!(matches!(
+            parse_sheet_reference("A1:B99999999999999999999999"),
+            Err(ParseSheetReferenceError::RowOutOfRange { .. })
+        ));
+    }
+
+    #[test]
+    fn r1c1_past_limits() {
+        assert_eq!(
+            parse_sheet_reference("R1C16385"),
+            Err(ParseSheetReferenceError::ColumnOutOfRange {
+                column: "XFE".to_owned(),
+                last: "XFD".to_owned()
+            })
+        );
+        assert!(matches!(
+            parse_sheet_reference("R1048577C1"),
+            Err(ParseSheetReferenceError::RowOutOfRange { .. })
+        ));
+    }
+
+    #[test]
+    fn reversed_range() {
+        assert_eq!(
+            parse_sheet_reference("B5:A1"),
+            Ok(sr(None, range(0, 0, 1, 4)))
+        );
+        assert_eq!(
+            parse_sheet_reference("A5:B1"),
+            Ok(sr(None, range(0, 0, 1, 4)))
+        );
+    }
+
+    #[test]
+    fn reversed_open_range() {
+        assert_eq!(
+            parse_sheet_reference("D:A"),
+            Ok(sr(None, open(col(0), col(3))))
+        );
+        assert_eq!(
+            parse_sheet_reference("10:1"),
+            Ok(sr(None, open(row(0), row(9))))
+        );
+        assert_eq!(
+            parse_sheet_reference("D5:A"),
+            Ok(sr(None, open(full(0, 4), col(3))))
+        );
+        assert_eq!(
+            parse_sheet_reference("R100C6:R2C1"),
+            Ok(sr(None, range(0, 1, 5, 99)))
+        );
+    }
+
+    #[test]
+    fn xls_limits() {
+        let xls = |s: &str| parse_sheet_reference(s).unwrap().check_limits(SheetLimits::XLS);
+        assert_eq!(xls("A1:IV65536"), Ok(()));
+        assert_eq!(xls("A:IV"), Ok(()));
+        assert_eq!(
+            xls("A1:IW2"),
+            Err(ParseSheetReferenceError::ColumnOutOfRange {
+                column: "IW".to_owned(),
+                last: "IV".to_owned()
+            })
+        );
+        assert_eq!(
+            xls("A65537"),
+            Err(ParseSheetReferenceError::RowOutOfRange {
+                row: 65537,
+                last: 65536
+            })
+        );
+    }
+
+    #[test]
+    fn error_messages() {
+        let message = |s: &str| parse_sheet_reference(s).unwrap_err().to_string();
+        assert_eq!(message("A1:XFE2"), "column XFE is past the last column XFD");
+        assert_eq!(message("A0"), "row 0 is outside rows 1 to 1048576");
+        assert_eq!(message("A1:é2"), "invalid column name 'é'");
+    }
+
+    // ═══════════════════════════════════════════
+    //  Multiple areas (A1:B5,D1:D5)
+    // ═══════════════════════════════════════════
+
+    #[test]
+    fn areas_single() {
+        assert_eq!(
+            parse_sheet_areas("A1:B5"),
+            Ok(vec![sr(None, range(0, 0, 1, 4))])
+        );
+    }
+
+    #[test]
+    fn areas_two_ranges() {
+        assert_eq!(
+            parse_sheet_areas("A1:B5,D1:D5"),
+            Ok(vec![sr(None, range(0, 0, 1, 4)), sr(None, range(3, 0, 3, 4))])
+        );
+    }
+
+    #[test]
+    fn areas_different_sheets() {
+        assert_eq!(
+            parse_sheet_areas("Sheet1!A1:B2,Sheet2!C3"),
+            Ok(vec![
+                sr(Some("Sheet1"), range(0, 0, 1, 1)),
+                sr(Some("Sheet2"), cell(2, 2)),
+            ])
+        );
+    }
+
+    #[test]
+    fn areas_inherit_sheet() {
+        assert_eq!(
+            parse_sheet_areas("Sheet1!A1, C3:D"),
+            Ok(vec![
+                sr(Some("Sheet1"), cell(0, 0)),
+                sr(Some("Sheet1"), open(full(2, 2), col(3))),
+            ])
+        );
+    }
+
+    #[test]
+    fn areas_quoted_sheet_with_comma() {
+        assert_eq!(
+            parse_sheet_areas("'Q1, Q2'!A1,B2"),
+            Ok(vec![
+                sr(Some("Q1, Q2"), cell(0, 0)),
+                sr(Some("Q1, Q2"), cell(1, 1)),
+            ])
+        );
+    }
+
+    #[test]
+    fn areas_errors() {
+        assert_eq!(parse_sheet_areas(" "), Err(ParseSheetReferenceError::Empty));
+        assert_eq!(parse_sheet_areas("A1,"), Err(ParseSheetReferenceError::Empty));
+        assert!(parse_sheet_areas("A1,!!!").is_err());
+    }
+
+    #[test]
+    fn single_reference_rejects_areas() {
+        assert_eq!(
+            parse_sheet_reference("A1:B5,D1:D5"),
+            Err(ParseSheetReferenceError::MultipleAreas)
+        );
+        assert_eq!(
+            parse_sheet_reference("'Q1, Q2'!A1"),
+            Ok(sr(Some("Q1, Q2"), cell(0, 0)))
+        );
+    }
+
+    // ═══════════════════════════════════════════
+    //  R1C1 notation
+    // ═══════════════════════════════════════════
+
+    #[test]
+    fn r1c1_cell() {
+        assert_eq!(parse_sheet_reference("R2C1"), Ok(sr(None, cell(0, 1))));
+    }
+
+    #[test]
+    fn r1c1_cell_lowercase() {
+        assert_eq!(parse_sheet_reference("r10c27"), Ok(sr(None, cell(26, 9))));
+    }
+
+    #[test]
+    fn r1c1_range() {
+        assert_eq!(
+            parse_sheet_reference("R2C1:R100C6"),
+            Ok(sr(None, range(0, 1, 5, 99)))
+        );
+    }
+
+    #[test]
+    fn r1c1_sheet_range() {
+        assert_eq!(
+            parse_sheet_reference("Sheet1!R1C1:R5C2"),
+            Ok(sr(Some("Sheet1"), range(0, 0, 1, 4)))
+        );
+    }
+
+    #[test]
+    fn r1c1_quoted_sheet_cell() {
+        assert_eq!(
+            parse_sheet_reference("'My Sheet'!R3C2"),
+            Ok(sr(Some("My Sheet"), cell(1, 2)))
+        );
+    }
+
+    #[test]
+    fn r1c1_whole_row() {
+        assert_eq!(
+            parse_sheet_reference("R5C"),
+            Ok(sr(None, open(row(4), row(4))))
+        );
+    }
+
+    #[test]
+    fn r1c1_row_range() {
+        assert_eq!(
+            parse_sheet_reference("R5C:R10C"),
+            Ok(sr(None, open(row(4), row(9))))
+        );
+    }
+
+    #[test]
+    fn r1c1_column_range() {
+        assert_eq!(
+            parse_sheet_reference("R1C3:C5"),
+            Ok(sr(None, open(full(2, 0), col(4))))
+        );
+        assert_eq!(
+            parse_r1c1_reference("RC3:RC5"),
+            Ok(sr(None, open(col(2), col(4))))
+        );
+    }
+
+    #[test]
+    fn r1c1_full_start_col_end() {
+        assert_eq!(
+            parse_sheet_reference("R2C1:C6"),
+            Ok(sr(None, open(full(0, 1), col(5))))
+        );
+    }
+
+    #[test]
+    fn r1c1_ambiguous_prefers_a1() {
+        // Valid in both notations: read as the A1 cells C3:C5
+        assert_eq!(
+            parse_sheet_reference("C3:C5"),
+            Ok(sr(None, range(2, 2, 2, 4)))
+        );
+        assert_eq!(parse_sheet_reference("R5"), Ok(sr(None, cell(17, 4))));
+        assert_eq!(parse_sheet_reference("RC3"), Ok(sr(None, cell(470, 2))));
+    }
+
+    #[test]
+    fn r1c1_strict_columns() {
+        assert_eq!(
+            parse_r1c1_reference("C3:C5"),
+            Ok(sr(None, open(col(2), col(4))))
+        );
+        assert_eq!(
+            parse_r1c1_reference("C3"),
+            Ok(sr(None, open(col(2), col(2))))
+        );
+    }
+
+    #[test]
+    fn r1c1_strict_rows() {
+        assert_eq!(
+            parse_r1c1_reference("R5:R10"),
+            Ok(sr(None, open(row(4), row(9))))
+        );
+    }
+
+    #[test]
+    fn r1c1_strict_rejects_a1() {
+        assert!(parse_r1c1_reference("A1").is_err());
+        assert!(parse_r1c1_reference("B2:C5").is_err());
+    }
+
+    #[test]
+    fn r1c1_errors() {
+        assert!(parse_sheet_reference("R0C1").is_err());
+        assert!(parse_sheet_reference("R1C0").is_err());
+        assert!(parse_sheet_reference("RC").is_err());
+        assert!(parse_sheet_reference("R1C1X").is_err());
+        assert!(parse_sheet_reference("R+1C1").is_err());
+        assert!(parse_r1c1_reference("").is_err());
+        assert!(matches!(
+            parse_r1c1_reference("R[-1]C"),
+            Err(ParseSheetReferenceError::InvalidReference(msg)) if msg.contains("relative")
+        ));
+    }
+
+    #[test]
+    fn addresses() {
+        assert_eq!(format_address(0, 0, None, false), "A1");
+        assert_eq!(format_address(27, 9, None, true), "$AB$10");
+        assert_eq!(format_address(1, 2, Some("Sheet1"), false), "Sheet1!B3");
+        assert_eq!(format_address(1, 2, Some("Bob's Data"), true), "'Bob''s Data'!$B$3");
+        assert_eq!(format_address(16383, 1048575, None, false), "XFD1048576");
+    }
+
+    fn area(s: &str) -> Area {
+        Area::from_target(&parse_sheet_reference(s).unwrap().target)
+    }
+
+    #[test]
+    fn areas_as_a1() {
+        assert_eq!(area("B3").to_a1(), "B3");
+        assert_eq!(area("B5:A1").to_a1(), "A1:B5");
+        assert_eq!(area("A:B").to_a1(), "A:B");
+        assert_eq!(area("2:5").to_a1(), "2:5");
+        assert_eq!(area("A2:D").to_a1(), "A2:D1048576");
+        assert_eq!(area("*:*").to_a1(), "A1:XFD1048576");
+        assert_eq!(area("C3:C3").to_a1(), "C3");
+    }
+
+    #[test]
+    fn area_size() {
+        assert_eq!((area("A1:C5").width(), area("A1:C5").height()), (3, 5));
+        assert_eq!((area("B:B").width(), area("B:B").height()), (1, 1048576));
+    }
+
+    #[test]
+    fn area_contains() {
+        assert!(area("A1:D10").contains(&area("B2")));
+        assert!(area("A1:D10").contains(&area("B2:D10")));
+        assert!(!area("A1:D10").contains(&area("B2:E3")));
+        assert!(area("B:B").contains(&area("B1000000")));
+        assert!(!area("B:B").contains(&area("C1")));
+        assert!(area("3:4").contains(&area("XFD4")));
+    }
+
+    #[test]
+    fn area_intersect() {
+        assert_eq!(area("A1:C3").intersect(&area("B2:D4")), Some(area("B2:C3")));
+        assert_eq!(area("A1:C3").intersect(&area("C3:D4")), Some(area("C3")));
+        assert_eq!(area("A1:B2").intersect(&area("C3:D4")), None);
+        assert_eq!(area("B:C").intersect(&area("2:3")), Some(area("B2:C3")));
+    }
+
+    #[test]
+    fn area_offset() {
+        let a = area("B2:D3");
+        assert_eq!(a.offset(2, 1, a.height(), a.width()), Some(area("C4:E5")));
+        assert_eq!(a.offset(-1, -1, a.height(), a.width()), Some(area("A1:C2")));
+        assert_eq!(a.offset(2, 0, 5, 3), Some(area("B4:D8")));
+        assert_eq!(a.offset(-2, 0, 1, 1), None);
+        assert_eq!(a.offset(0, 16382, 1, 1), Some(area("XFD2")));
+        assert_eq!(a.offset(0, 16383, 1, 1), None);
+        assert_eq!(area("A:A").offset(1, 0, 1048576, 1), None);
+        assert_eq!(a.offset(i64::MAX, 0, 1, 1), None);
+    }
+
+    #[test]
+    fn references_as_json() {
+        let json = |s: &str| reference_json(&parse_sheet_reference(s).unwrap()).to_string();
+        assert_eq!(
+            json("Sheet1!B5:A1"),
+            r#"{"sheet":"Sheet1","start":{"column":0,"row":0},"end":{"column":1,"row":4},"address":"A1:B5"}"#
+        );
+        assert_eq!(
+            json("C3"),
+            r#"{"sheet":null,"start":{"column":2,"row":2},"end":{"column":2,"row":2},"address":"C3"}"#
+        );
+        assert_eq!(
+            json("A2:D*"),
+            r#"{"sheet":null,"start":{"column":0,"row":1},"end":{"column":3,"row":null},"address":"A2:D"}"#
+        );
+    }
+
+    #[test]
+    fn r1c1_to_a1() {
+        let a1 = |s: &str| format_a1_target(&parse_r1c1_reference(s).unwrap().target);
+        assert_eq!(a1("R2C1"), "A2");
+        assert_eq!(a1("R2C1:R100C6"), "A2:F100");
+        assert_eq!(a1("R5C"), "5:5");
+        assert_eq!(a1("C3:C5"), "C:E");
+        assert_eq!(a1("R2C1:C6"), "A2:F");
+        assert_eq!(a1("R1C27"), "AA1");
+    }
 }
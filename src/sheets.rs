@@ -1,4 +1,3 @@
-use calamine::Reader;
 use sqlite_loadable::prelude::*;
 use sqlite_loadable::{
     api,
@@ -8,6 +7,8 @@ use sqlite_loadable::{
 
 use std::{mem, os::raw::c_int};
 
+use crate::workbook::Workbook;
+
 static CREATE_SQL: &str = "CREATE TABLE x(name, visible, workbook hidden)";
 enum Columns {
     Name,
@@ -80,7 +81,7 @@ pub struct SheetsCursor {
     /// Base class. Must be first
     base: sqlite3_vtab_cursor,
     rowid: i64,
-    workbook: Option<calamine::Sheets<std::io::Cursor<Vec<u8>>>>,
+    workbook: Option<Workbook>,
 }
 impl SheetsCursor {
     fn new() -> SheetsCursor {
@@ -103,7 +104,9 @@ impl VTabCursor for SheetsCursor {
         let raw = api::value_blob(values.first().expect("1st min constraint is required"));
         let data = raw.to_vec();
         self.workbook =
-            Some(calamine::open_workbook_auto_from_rs(std::io::Cursor::new(data)).unwrap());
+            Some(Workbook::open(data).map_err(|e| {
+                sqlite_loadable::Error::new_message(format!("cannot open workbook: {e}"))
+            })?);
         Ok(())
     }
 
@@ -0,0 +1,400 @@
+//! Open workbook blobs, including CSV and TSV text read as a single sheet.
+
+use std::io::{Cursor, Read};
+
+use calamine::{
+    open_workbook_from_rs, Data, Range, Reader, Sheet, SheetType, SheetVisible, Sheets,
+};
+use serde_json::json;
+use sqlite_loadable::prelude::*;
+use sqlite_loadable::api;
+use thiserror::Error;
+use zip::ZipArchive;
+
+use crate::parser::SheetLimits;
+
+/// Name of the only sheet of a CSV or TSV file.
+pub const TEXT_SHEET_NAME: &str = "Sheet1";
+
+#[derive(Error, Debug)]
+pub enum OpenError {
+    #[error("workbook is encrypted with a password")]
+    Encrypted,
+    #[error("{0}")]
+    Excel(#[from] calamine::Error),
+    #[error("invalid delimited text: {0}")]
+    Text(#[from] csv::Error),
+}
+
+/// A workbook opened from a blob.
+pub enum Workbook {
+    Excel(Sheets<Cursor<Vec<u8>>>),
+    /// CSV or TSV text, exposed as a single sheet
+    Text { sheet: Sheet, range: Range<Data> },
+}
+
+impl Workbook {
+    /// Open an Excel or OpenDocument workbook, or CSV/TSV text detected by
+    /// sniffing its leading lines.
+    pub fn open(data: Vec<u8>) -> Result<Workbook, OpenError> {
+        if is_encrypted(&data) {
+            return Err(OpenError::Encrypted);
+        }
+        let format = detect_format(&data);
+        let delimiter = match format {
+            Some(Format::Csv) => b',',
+            Some(Format::Tsv) => b'\t',
+            _ => return Ok(Workbook::Excel(open_excel(format, data)?)),
+        };
+        Ok(Workbook::Text {
+            sheet: Sheet {
+                name: TEXT_SHEET_NAME.to_owned(),
+                typ: SheetType::WorkSheet,
+                visible: SheetVisible::Visible,
+            },
+            range: read_delimited(&data, delimiter)?,
+        })
+    }
+
+    /// The size of a sheet in this workbook's file format.
+    pub fn limits(&self) -> SheetLimits {
+        match self {
+            Workbook::Excel(Sheets::Xls(_)) => SheetLimits::XLS,
+            _ => SheetLimits::XLSX,
+        }
+    }
+
+    pub fn sheet_names(&self) -> Vec<String> {
+        self.sheets_metadata().iter().map(|s| s.name.clone()).collect()
+    }
+
+    pub fn sheets_metadata(&self) -> &[Sheet] {
+        match self {
+            Workbook::Excel(workbook) => workbook.sheets_metadata(),
+            Workbook::Text { sheet, .. } => std::slice::from_ref(sheet),
+        }
+    }
+
+    pub fn worksheet_range(&mut self, name: &str) -> Result<Range<Data>, OpenError> {
+        match self {
+            Workbook::Excel(workbook) => Ok(workbook.worksheet_range(name)?),
+            Workbook::Text { sheet, range } if sheet.name == name => Ok(range.clone()),
+            Workbook::Text { .. } => Err(calamine::Error::Msg("sheet not found").into()),
+        }
+    }
+}
+
+/// Open a workbook with the reader for its detected format, so that errors
+/// come from that reader rather than a failed guess at every format.
+fn open_excel(
+    format: Option<Format>,
+    data: Vec<u8>,
+) -> Result<Sheets<Cursor<Vec<u8>>>, calamine::Error> {
+    let data = Cursor::new(data);
+    Ok(match format {
+        Some(Format::Xlsx | Format::Xlsm) => Sheets::Xlsx(open_workbook_from_rs(data)?),
+        Some(Format::Xlsb) => Sheets::Xlsb(open_workbook_from_rs(data)?),
+        Some(Format::Xls) => Sheets::Xls(open_workbook_from_rs(data)?),
+        Some(Format::Ods) => Sheets::Ods(open_workbook_from_rs(data)?),
+        _ => calamine::open_workbook_auto_from_rs(data)?,
+    })
+}
+
+/// File format of a workbook blob.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Xlsx,
+    Xlsm,
+    Xlsb,
+    Xls,
+    Ods,
+    Csv,
+    Tsv,
+}
+
+impl Format {
+    pub fn name(self) -> &'static str {
+        match self {
+            Format::Xlsx => "xlsx",
+            Format::Xlsm => "xlsm",
+            Format::Xlsb => "xlsb",
+            Format::Xls => "xls",
+            Format::Ods => "ods",
+            Format::Csv => "csv",
+            Format::Tsv => "tsv",
+        }
+    }
+}
+
+/// Compound file (OLE) signature, used by `.xls` and encrypted workbooks.
+const CFB_SIGNATURE: &[u8] = b"\xD0\xCF\x11\xE0\xA1\xB1\x1A\xE1";
+
+/// Whether a blob is an encrypted Office package: a compound file holding
+/// an `EncryptionInfo` stream rather than a workbook.
+pub fn is_encrypted(data: &[u8]) -> bool {
+    data.starts_with(CFB_SIGNATURE)
+        && cfb::CompoundFile::open(Cursor::new(data))
+            .is_ok_and(|file| file.is_stream("/EncryptionInfo"))
+}
+
+/// Detect the format of a workbook blob from its contents, not its name.
+/// `None` when it isn't a workbook, or when it is encrypted.
+pub fn detect_format(data: &[u8]) -> Option<Format> {
+    if data.starts_with(CFB_SIGNATURE) {
+        return (!is_encrypted(data)).then_some(Format::Xls);
+    }
+    if data.starts_with(b"PK\x03\x04") {
+        return detect_zip_format(data);
+    }
+    match sniff_delimiter(data)? {
+        b'\t' => Some(Format::Tsv),
+        _ => Some(Format::Csv),
+    }
+}
+
+/// Format of a zip package, from its OpenDocument mimetype or the content
+/// type of its workbook part.
+fn detect_zip_format(data: &[u8]) -> Option<Format> {
+    let mut archive = ZipArchive::new(Cursor::new(data)).ok()?;
+    let mut read = |name: &str| {
+        let mut text = String::new();
+        archive.by_name(name).ok()?.read_to_string(&mut text).ok()?;
+        Some(text)
+    };
+    if let Some(mimetype) = read("mimetype") {
+        return (mimetype.trim() == "application/vnd.oasis.opendocument.spreadsheet")
+            .then_some(Format::Ods);
+    }
+    let content_types = read("[Content_Types].xml")?;
+    if content_types.contains("application/vnd.ms-excel.sheet.binary.macroEnabled.main") {
+        Some(Format::Xlsb)
+    } else if content_types.contains("application/vnd.ms-excel.sheet.macroEnabled.main+xml") {
+        Some(Format::Xlsm)
+    } else if content_types.contains("spreadsheetml.sheet.main+xml")
+        || content_types.contains("spreadsheetml.template.main+xml")
+    {
+        Some(Format::Xlsx)
+    } else {
+        None
+    }
+}
+
+/// Number of leading records compared when sniffing a delimiter.
+const SNIFF_RECORDS: usize = 5;
+
+/// The delimiter of CSV or TSV text: a tab or comma that splits the first
+/// record into at least two fields, and another of the leading records into
+/// as many (unless there is only one), with tabs tried first. `None` for binary data, which is left to calamine, and for
+/// text that isn't delimited.
+fn sniff_delimiter(data: &[u8]) -> Option<u8> {
+    // zip (xlsx, xlsb, ods) and compound file (xls) signatures
+    if data.starts_with(b"PK\x03\x04") || data.starts_with(b"\xD0\xCF\x11\xE0") {
+        return None;
+    }
+    let text = std::str::from_utf8(data.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(data)).ok()?;
+    if text.contains(|c: char| c.is_control() && !c.is_ascii_whitespace()) {
+        return None;
+    }
+    [b'\t', b','].into_iter().find(|&delimiter| {
+        matches!(
+            field_counts(text, delimiter).as_deref(),
+            Some([first, rest @ ..]) if *first > 1 && (rest.is_empty() || rest.contains(first))
+        )
+    })
+}
+
+/// Number of fields in each leading record of `text`, `None` when it can't
+/// be read as delimited text.
+fn field_counts(text: &str, delimiter: u8) -> Option<Vec<usize>> {
+    csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(text.as_bytes())
+        .records()
+        .take(SNIFF_RECORDS)
+        .map(|record| record.ok().map(|record| record.len()))
+        .collect()
+}
+
+/// Read delimited text into a range of cells.
+fn read_delimited(data: &[u8], delimiter: u8) -> Result<Range<Data>, csv::Error> {
+    let data = data.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(data);
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(data);
+    let mut rows: Vec<Vec<Data>> = Vec::new();
+    for record in reader.records() {
+        rows.push(record?.iter().map(text_to_data).collect());
+    }
+    let width = rows.iter().map(Vec::len).max().unwrap_or(0);
+    if rows.is_empty() || width == 0 {
+        return Ok(Range::empty());
+    }
+    let mut range = Range::new((0, 0), (rows.len() as u32 - 1, width as u32 - 1));
+    for (r, row) in rows.into_iter().enumerate() {
+        for (c, data) in row.into_iter().enumerate() {
+            range.set_value((r as u32, c as u32), data);
+        }
+    }
+    Ok(range)
+}
+
+/// Infer the cell value of a CSV field: numbers, booleans and ISO dates are
+/// typed like the cells of a workbook, everything else stays text.
+fn text_to_data(field: &str) -> Data {
+    let trimmed = field.trim();
+    if trimmed.is_empty() {
+        return Data::Empty;
+    }
+    let digits = trimmed.strip_prefix('-').unwrap_or(trimmed);
+    // leading zeros are kept as text, as in zip codes or IDs
+    let leading_zero = digits.len() > 1 && digits.starts_with('0') && !digits.starts_with("0.");
+    if digits.starts_with(|c: char| c.is_ascii_digit() || c == '.') && !leading_zero {
+        if let Ok(v) = trimmed.parse::<i64>() {
+            return Data::Int(v);
+        }
+        if let Ok(v) = trimmed.parse::<f64>() {
+            return Data::Float(v);
+        }
+    }
+    if trimmed.eq_ignore_ascii_case("true") {
+        return Data::Bool(true);
+    }
+    if trimmed.eq_ignore_ascii_case("false") {
+        return Data::Bool(false);
+    }
+    if crate::export::is_iso_date(trimmed) {
+        return Data::DateTimeIso(trimmed.to_owned());
+    }
+    Data::String(field.to_owned())
+}
+
+/// `xl_format(blob)`: `xlsx`, `xlsm`, `xlsb`, `xls`, `ods`, `csv`, `tsv`,
+/// or NULL.
+pub fn xl_format(context: *mut sqlite3_context, values: &[*mut sqlite3_value]) -> sqlite_loadable::Result<()> {
+    match detect_format(api::value_blob(&values[0])) {
+        Some(format) => api::result_text(context, format.name())?,
+        None => api::result_null(context),
+    }
+    Ok(())
+}
+
+/// `xl_validate(blob)`: whether a blob can be opened, and why not.
+pub fn xl_validate(context: *mut sqlite3_context, values: &[*mut sqlite3_value]) -> sqlite_loadable::Result<()> {
+    let data = api::value_blob(&values[0]);
+    let format = detect_format(data);
+    let encrypted = is_encrypted(data);
+    let (sheets, error) = match Workbook::open(data.to_vec()) {
+        Ok(workbook) => (Some(workbook.sheets_metadata().len()), None),
+        Err(e) => (None, Some(e.to_string())),
+    };
+    api::result_json(
+        context,
+        json!({
+            "valid": error.is_none(),
+            "format": format.map(Format::name),
+            "sheets": sheets,
+            "encrypted": encrypted,
+            "error": error,
+        }),
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn s(v: &str) -> Data {
+        Data::String(v.to_owned())
+    }
+
+    #[test]
+    fn sniffing() {
+        assert_eq!(sniff_delimiter(b"a,b\n1,2"), Some(b','));
+        assert_eq!(sniff_delimiter(b"a\tb, c\n1\t2"), Some(b'\t'));
+        assert_eq!(sniff_delimiter(b"\xEF\xBB\xBFa,b"), Some(b','));
+        assert_eq!(sniff_delimiter(b"\"Smith, J\",1\nLee,2"), Some(b','));
+        assert_eq!(sniff_delimiter(b"a,b\n1,2,3"), None);
+        assert_eq!(sniff_delimiter(b"id\n1001\n1002"), None);
+        assert_eq!(sniff_delimiter(b""), None);
+        assert_eq!(sniff_delimiter(b"\x01\x02"), None);
+        assert_eq!(sniff_delimiter(b"PK\x03\x04a,b"), None);
+        assert_eq!(sniff_delimiter(b"\xff\xfea,b"), None);
+        assert_eq!(sniff_delimiter(include_bytes!("../tests/students.xlsx")), None);
+    }
+
+    #[test]
+    fn values() {
+        assert_eq!(text_to_data("42"), Data::Int(42));
+        assert_eq!(text_to_data("-1.5"), Data::Float(-1.5));
+        assert_eq!(text_to_data(".5"), Data::Float(0.5));
+        assert_eq!(text_to_data("0.5"), Data::Float(0.5));
+        assert_eq!(text_to_data("0"), Data::Int(0));
+        assert_eq!(text_to_data("007"), s("007"));
+        assert_eq!(text_to_data("inf"), s("inf"));
+        assert_eq!(text_to_data("TRUE"), Data::Bool(true));
+        assert_eq!(text_to_data("2024-01-15"), Data::DateTimeIso("2024-01-15".to_owned()));
+        assert_eq!(text_to_data("2024-13-45"), s("2024-13-45"));
+        assert_eq!(text_to_data(" "), Data::Empty);
+        assert_eq!(text_to_data("North"), s("North"));
+    }
+
+    #[test]
+    fn text_workbook() {
+        let csv = b"region,amount\nNorth,10\n\"South, East\",20.5,x\n";
+        let mut workbook = Workbook::open(csv.to_vec()).unwrap();
+        assert_eq!(workbook.sheet_names(), vec![TEXT_SHEET_NAME.to_owned()]);
+        let range = workbook.worksheet_range(TEXT_SHEET_NAME).unwrap();
+        assert_eq!(range.get_size(), (3, 3));
+        assert_eq!(range.get_value((1, 1)), Some(&Data::Int(10)));
+        assert_eq!(range.get_value((2, 0)), Some(&s("South, East")));
+        assert_eq!(range.get_value((1, 2)), Some(&Data::Empty));
+        assert!(workbook.worksheet_range("other").is_err());
+
+        let mut tsv = Workbook::open(b"a\tb\n1\t2\n".to_vec()).unwrap();
+        let tsv = tsv.worksheet_range(TEXT_SHEET_NAME).unwrap();
+        assert_eq!(tsv.get_value((1, 1)), Some(&Data::Int(2)));
+    }
+
+    #[test]
+    fn formats() {
+        let format = |data: &[u8]| detect_format(data).map(Format::name);
+        assert_eq!(format(include_bytes!("../tests/students.xlsx")), Some("xlsx"));
+        assert_eq!(format(b"a,b\n1,2"), Some("csv"));
+        assert_eq!(format(b"a\tb\n1\t2"), Some("tsv"));
+        assert_eq!(format(b"not a workbook"), None);
+        assert_eq!(format(b"{\n  \"region\": \"North\",\n  \"amount\": 10\n}"), None);
+        assert_eq!(format(b"<html>\n<body>\n<p>Hello, world</p>\n</body>\n</html>"), None);
+        assert_eq!(format(b"\x01\x02"), None);
+        assert_eq!(format(b"PK\x03\x04 truncated"), None);
+
+        let compound_file = |stream: &str, contents: &[u8]| {
+            use std::io::Write;
+            let mut file = cfb::CompoundFile::create(Cursor::new(Vec::new())).unwrap();
+            file.create_stream(stream).unwrap().write_all(contents).unwrap();
+            file.flush().unwrap();
+            file.into_inner().into_inner()
+        };
+        // an `.xls` whose cells mention the stream name isn't encrypted
+        let text: Vec<u8> = "EncryptionInfo".encode_utf16().flat_map(u16::to_le_bytes).collect();
+        let xls = compound_file("/Workbook", &text);
+        assert_eq!(format(&xls), Some("xls"));
+        assert!(!is_encrypted(&xls));
+
+        let encrypted = compound_file("/EncryptionInfo", b"");
+        assert!(is_encrypted(&encrypted));
+        assert_eq!(format(&encrypted), None);
+        assert!(matches!(Workbook::open(encrypted), Err(OpenError::Encrypted)));
+    }
+
+    #[test]
+    fn excel_workbook() {
+        let workbook = Workbook::open(include_bytes!("../tests/students.xlsx").to_vec()).unwrap();
+        assert_eq!(workbook.sheet_names()[0], "students");
+        assert!(Workbook::open(b"\x01\x02".to_vec()).is_err());
+    }
+}
@@ -1,17 +1,61 @@
+use serde_json::Value;
+use sqlite_loadable::ext::sqlite3ext_context_db_handle;
 use sqlite_loadable::prelude::*;
 use sqlite_loadable::vtab_argparse::{parse_argument, Argument, ColumnDeclaration, ConfigOptionValue};
 use sqlite_loadable::{
     api,
     api::ColumnAffinity,
-    table::{BestIndexError, ConstraintOperator, IndexInfo, VTab, VTabArguments, VTabCursor},
+    table::{
+        BestIndexError, ConstraintOperator, IndexInfo, UpdateOperation, VTab, VTabArguments,
+        VTabCursor, VTabWriteable, VTabWriteableWithTransactions,
+    },
     Error, Result,
 };
+use std::borrow::Cow;
+use std::time::{Duration, SystemTime};
 use std::{mem, os::raw::c_int};
 
-use calamine::{Data, DataType, Reader};
+use calamine::{Data, DataType, Range};
+use sha2::{Digest, Sha256};
 
+use crate::cache::{self, quote_identifier, quote_table_name, Statement};
+use crate::crypto;
+use crate::detect::detect_table;
 use crate::parser::column_idx_to_name;
+use crate::patch::{self, value_to_data, CellEdits};
 use crate::sheet_range::{parse_sheet_reference, SheetTarget};
+use crate::workbook::Workbook;
+
+/// Coerce a cell value to match a column's declared type when possible.
+fn with_affinity<'a>(data: &'a Data, affinity: &ColumnAffinity) -> Cow<'a, Data> {
+    let coerced = match affinity {
+        ColumnAffinity::Integer => match data {
+            Data::Float(v) => Data::Int(*v as i64),
+            Data::Bool(v) => Data::Int(i64::from(*v)),
+            Data::String(s) => match s.parse::<i64>() {
+                Ok(v) => Data::Int(v),
+                Err(_) => return Cow::Borrowed(data),
+            },
+            _ => return Cow::Borrowed(data),
+        },
+        ColumnAffinity::Real => match data {
+            Data::Int(v) => Data::Float(*v as f64),
+            Data::String(s) => match s.parse::<f64>() {
+                Ok(v) => Data::Float(v),
+                Err(_) => return Cow::Borrowed(data),
+            },
+            _ => return Cow::Borrowed(data),
+        },
+        ColumnAffinity::Text => match data {
+            Data::Int(v) => Data::String(v.to_string()),
+            Data::Float(v) => Data::String(v.to_string()),
+            Data::Bool(v) => Data::String(if *v { "true" } else { "false" }.to_owned()),
+            _ => return Cow::Borrowed(data),
+        },
+        _ => return Cow::Borrowed(data),
+    };
+    Cow::Owned(coerced)
+}
 
 /// Apply column affinity to a calamine Data value.
 /// Coerces the value to match the declared type when possible.
@@ -20,73 +64,217 @@ fn result_xl_data_with_affinity(
     data: &Data,
     affinity: &ColumnAffinity,
 ) -> Result<()> {
-    match affinity {
-        ColumnAffinity::Integer => match data {
-            Data::Int(v) => api::result_int64(context, *v),
-            Data::Float(v) => api::result_int64(context, *v as i64),
-            Data::Bool(v) => api::result_int64(context, if *v { 1 } else { 0 }),
-            Data::String(s) => {
-                if let Ok(v) = s.parse::<i64>() {
-                    api::result_int64(context, v);
-                } else {
-                    api::result_text(context, s)?;
-                }
-            }
-            _ => crate::result_xl_data(context, data)?,
-        },
-        ColumnAffinity::Real => match data {
-            Data::Float(v) => api::result_double(context, *v),
-            Data::Int(v) => api::result_double(context, *v as f64),
-            Data::String(s) => {
-                if let Ok(v) = s.parse::<f64>() {
-                    api::result_double(context, v);
-                } else {
-                    api::result_text(context, s)?;
-                }
-            }
-            _ => crate::result_xl_data(context, data)?,
-        },
-        ColumnAffinity::Text => match data {
-            Data::String(s) => api::result_text(context, s)?,
-            Data::Int(v) => api::result_text(context, v.to_string())?,
-            Data::Float(v) => api::result_text(context, v.to_string())?,
-            Data::Bool(v) => api::result_text(context, if *v { "true" } else { "false" })?,
-            Data::Empty => api::result_null(context),
-            _ => crate::result_xl_data(context, data)?,
-        },
-        _ => crate::result_xl_data(context, data)?,
-    }
-    Ok(())
+    crate::result_xl_data(context, &with_affinity(data, affinity))
 }
 
 #[repr(C)]
 pub struct XL0Table {
     base: sqlite3_vtab,
+    db: *mut sqlite3,
     filename: Option<String>,
+    /// Glob pattern when `filename` contains wildcards, expanded at query time
+    file_glob: Option<String>,
     sheet_name: Option<String>,
     start_row: u32,
     end_row: Option<u32>,
     start_col: u32,
-    end_col: Option<u32>,
+    /// End column as written in `range`, `None` when open-ended
+    range_end_col: Option<u32>,
+    /// Sheet column read for each declared column; `None` reads as NULL
+    columns: Vec<Option<u32>>,
+    /// Combined header text of each column when the schema was declared
+    header: Vec<Option<String>>,
+    /// Fingerprint of `filename` when it was last read (single file only)
+    fingerprint: Option<Fingerprint>,
+    /// What to do when the file's header no longer matches the schema
+    on_change: OnChange,
+    /// Password that the workbooks are encrypted with
+    password: Option<String>,
+    /// Qualified name of the `<name>_data` shadow table, when `cache=1`
+    cache_table: Option<String>,
+    /// Qualified name of the `<name>_meta` shadow table, holding the
+    /// fingerprint of the file the cached rows were copied from
+    cache_meta: Option<String>,
+    column_names: Vec<String>,
+    /// Rows written during the current transaction, saved to the file on commit
+    pending: Option<PendingEdits>,
     num_columns: usize,
-    has_headers: bool,
+    /// Number of stacked header rows at the top of the range (0 = no headers)
+    header_rows: u32,
     declared_types: Vec<Option<String>>,
+    /// End iteration at the first row whose columns are all empty
+    stop_at_blank: bool,
+    /// Skip rows whose columns are all empty
+    skip_blank_rows: bool,
     /// Index of the hidden "source" column (only set when filename is omitted)
     source_column_idx: Option<usize>,
+    /// Index of the hidden "_file" column, followed by "_sheet" (only set in glob mode)
+    file_column_idx: Option<usize>,
+}
+
+/// What xl0 does when a changed file's header no longer matches the schema.
+#[derive(Clone, Copy, PartialEq)]
+enum OnChange {
+    /// Fail the query with a "schema drift" error.
+    Error,
+    /// Find the declared columns again by header name.
+    Rebuild,
+}
+
+/// Identity of a file's contents, so edits are noticed between queries.
+#[derive(Clone, PartialEq)]
+struct Fingerprint {
+    modified: Option<SystemTime>,
+    size: u64,
+    /// Hex SHA-256 digest, which stays the same across builds so it can be
+    /// saved with a cached copy
+    hash: String,
+}
+
+impl Fingerprint {
+    /// Whether `path` still has the modification time and size seen last.
+    fn metadata_matches(&self, path: &str) -> bool {
+        match std::fs::metadata(path) {
+            Ok(m) => self.modified.is_some() && m.modified().ok() == self.modified && m.len() == self.size,
+            Err(_) => false,
+        }
+    }
+
+    /// Fingerprint `data` read from `path`. The hash is only recomputed when
+    /// the modification time or size differ from `previous`.
+    fn new(path: &str, data: &[u8], previous: Option<&Fingerprint>) -> Fingerprint {
+        let modified = std::fs::metadata(path).and_then(|m| m.modified()).ok();
+        let size = data.len() as u64;
+        let hash = match previous {
+            Some(p) if p.modified.is_some() && p.modified == modified && p.size == size => p.hash.clone(),
+            _ => Sha256::digest(data).iter().map(|b| format!("{b:02x}")).collect(),
+        };
+        Fingerprint {
+            modified,
+            size,
+            hash,
+        }
+    }
+
+    /// The fingerprint saved in a `<name>_meta` shadow table, if any.
+    fn load(db: *mut sqlite3, table: &str) -> Result<Option<Fingerprint>> {
+        let stmt = Statement::prepare(db, &format!("SELECT modified, size, hash FROM {table}"))?;
+        if !stmt.step()? {
+            return Ok(None);
+        }
+        let modified = stmt.column_value(0);
+        let modified = (!api::value_is_null(&modified))
+            .then(|| SystemTime::UNIX_EPOCH + Duration::from_nanos(api::value_int64(&modified) as u64));
+        Ok(Some(Fingerprint {
+            modified,
+            size: api::value_int64(&stmt.column_value(1)) as u64,
+            hash: api::value_text(&stmt.column_value(2))?.to_owned(),
+        }))
+    }
+
+    /// Save the fingerprint in a `<name>_meta` shadow table, replacing the
+    /// one saved before.
+    fn store(&self, db: *mut sqlite3, table: &str) -> Result<()> {
+        cache::execute(db, &format!("DELETE FROM {table}"))?;
+        let stmt = Statement::prepare(db, &format!("INSERT INTO {table} VALUES (?, ?, ?)"))?;
+        let modified = self
+            .modified
+            .and_then(|m| m.duration_since(SystemTime::UNIX_EPOCH).ok())
+            .and_then(|d| i64::try_from(d.as_nanos()).ok());
+        stmt.bind_data(1, &modified.map_or(Data::Empty, Data::Int))?;
+        stmt.bind_data(2, &Data::Int(self.size as i64))?;
+        stmt.bind_data(3, &Data::String(self.hash.clone()))?;
+        stmt.step()?;
+        Ok(())
+    }
+}
+
+/// Data rows of a sheet, each with its 0-based sheet row.
+type SheetRows = Vec<(u32, Vec<Data>)>;
+
+/// The rows of an xl0 table while a transaction writes to it.
+struct PendingEdits {
+    /// Sheet the rows are written back to
+    sheet: String,
+    /// 0-based sheet row of the first data row
+    first_row: u32,
+    /// Rows as they were read, from `first_row` to the last row of the table
+    original: Vec<Vec<Data>>,
+    /// Rows after the edits so far, each with the 0-based sheet row it was
+    /// read from, or a new row past the end of the table for inserted rows,
+    /// so that rowids stay the same for the whole transaction
+    rows: SheetRows,
+    /// 0-based sheet row given to the next inserted row
+    next_row: u32,
+    /// Number of sheet rows in use, from the top of the sheet
+    used_rows: u32,
+}
+
+impl PendingEdits {
+    fn position(&self, rowid: i64) -> Result<usize> {
+        u32::try_from(rowid - 1)
+            .ok()
+            .and_then(|row| self.rows.iter().position(|(r, _)| *r == row))
+            .ok_or_else(|| Error::new_message(format!("no row with rowid {rowid} in the table")))
+    }
+}
+
+// idx_num values passed from best_index to filter
+const IDX_FILENAME: c_int = 0;
+const IDX_SOURCE: c_int = 1;
+const IDX_FILE_CONSTRAINT: c_int = 2;
+const IDX_CACHE: c_int = 3;
+
+/// Whether a filename contains glob wildcards.
+fn is_glob(filename: &str) -> bool {
+    filename.contains(['*', '?', '['])
+}
+
+/// Expand a glob pattern into matching file paths, in sorted order.
+fn expand_glob(pattern: &str) -> Result<Vec<String>> {
+    let paths = glob::glob(pattern)
+        .map_err(|e| Error::new_message(format!("invalid filename pattern '{pattern}': {e}")))?;
+    let mut files: Vec<String> = paths
+        .filter_map(|p| p.ok())
+        .filter(|p| p.is_file())
+        .map(|p| p.to_string_lossy().into_owned())
+        .collect();
+    files.sort();
+    Ok(files)
 }
 
 impl<'vtab> VTab<'vtab> for XL0Table {
     type Aux = ();
     type Cursor = XL0Cursor;
 
+    fn create(
+        db: *mut sqlite3,
+        aux: Option<&Self::Aux>,
+        args: VTabArguments,
+    ) -> Result<(String, XL0Table)> {
+        let (sql, mut vtab) = Self::connect(db, aux, args)?;
+        // copy the sheet into the shadow table right away
+        if vtab.cache_table.is_some() {
+            vtab.refresh_cache()?;
+        }
+        Ok((sql, vtab))
+    }
+
     fn connect(
-        _db: *mut sqlite3,
+        db: *mut sqlite3,
         _aux: Option<&Self::Aux>,
         args: VTabArguments,
     ) -> Result<(String, XL0Table)> {
         let mut filename: Option<String> = None;
         let mut range_str: Option<String> = None;
-        let mut has_headers = false;
+        let mut password: Option<String> = None;
+        let mut header_rows: Option<u32> = None;
+        let mut infer_types: usize = 0;
+        let mut stop_at_blank = false;
+        let mut skip_blank_rows = false;
+        let mut header_case = HeaderCase::Preserve;
+        let mut on_change = OnChange::Error;
+        let mut cache = false;
         let mut explicit_columns: Vec<ColumnDeclaration> = Vec::new();
 
         for arg_str in &args.arguments {
@@ -112,13 +300,91 @@ impl<'vtab> VTab<'vtab> for XL0Table {
                             }
                         });
                     }
-                    "headers" => {
-                        has_headers = match config.value {
+                    "password" => {
+                        password = Some(match config.value {
+                            ConfigOptionValue::Quoted(s) | ConfigOptionValue::Bareword(s) => s,
+                            _ => return Err(Error::new_message("password must be a string")),
+                        });
+                    }
+                    "headers" | "header_rows" => {
+                        header_rows = Some(match config.value {
+                            ConfigOptionValue::Bareword(s) => {
+                                let truthy = ["true", "yes", "on"];
+                                let falsy = ["false", "no", "off"];
+                                if truthy.iter().any(|t| s.eq_ignore_ascii_case(t)) {
+                                    1
+                                } else if falsy.iter().any(|f| s.eq_ignore_ascii_case(f)) {
+                                    0
+                                } else {
+                                    s.parse().map_err(|_| {
+                                        Error::new_message(format!(
+                                            "{} must be a number of header rows, true or false",
+                                            config.key
+                                        ))
+                                    })?
+                                }
+                            }
+                            _ => 0,
+                        });
+                    }
+                    "header_case" => {
+                        header_case = match config.value {
+                            ConfigOptionValue::Quoted(s) | ConfigOptionValue::Bareword(s) => {
+                                match s.as_str() {
+                                    "preserve" => HeaderCase::Preserve,
+                                    "snake_case" => HeaderCase::Snake,
+                                    other => {
+                                        return Err(Error::new_message(format!(
+                                            "unknown header_case '{other}', expected 'preserve' or 'snake_case'"
+                                        )))
+                                    }
+                                }
+                            }
+                            _ => {
+                                return Err(Error::new_message("header_case must be a string"))
+                            }
+                        };
+                    }
+                    "on_change" => {
+                        on_change = match config.value {
+                            ConfigOptionValue::Quoted(s) | ConfigOptionValue::Bareword(s) => {
+                                match s.as_str() {
+                                    "error" => OnChange::Error,
+                                    "rebuild" => OnChange::Rebuild,
+                                    other => {
+                                        return Err(Error::new_message(format!(
+                                            "unknown on_change '{other}', expected 'error' or 'rebuild'"
+                                        )))
+                                    }
+                                }
+                            }
+                            _ => return Err(Error::new_message("on_change must be a string")),
+                        };
+                    }
+                    "stop_at_blank" | "skip_blank_rows" | "cache" => {
+                        let enabled = match config.value {
                             ConfigOptionValue::Bareword(s) => {
                                 s == "1" || s.eq_ignore_ascii_case("true")
                             }
                             _ => false,
                         };
+                        match config.key.as_str() {
+                            "stop_at_blank" => stop_at_blank = enabled,
+                            "skip_blank_rows" => skip_blank_rows = enabled,
+                            _ => cache = enabled,
+                        }
+                    }
+                    "infer_types" => {
+                        infer_types = match config.value {
+                            ConfigOptionValue::Bareword(s) => s.parse().map_err(|_| {
+                                Error::new_message("infer_types must be a non-negative integer")
+                            })?,
+                            _ => {
+                                return Err(Error::new_message(
+                                    "infer_types must be a non-negative integer",
+                                ))
+                            }
+                        };
                     }
                     other => {
                         return Err(Error::new_message(format!(
@@ -132,79 +398,77 @@ impl<'vtab> VTab<'vtab> for XL0Table {
             }
         }
 
-        // Parse range to get sheet + bounds
-        let (sheet_name, start_row, end_row, start_col, end_col) = match &range_str {
-            Some(r) => {
-                let parsed = parse_sheet_reference(r)
-                    .map_err(|e| Error::new_message(format!("invalid range: {e}")))?;
-                let sheet = parsed.sheet;
-                match parsed.target {
-                    SheetTarget::Range(r) => {
-                        (sheet, r.start.1, Some(r.end.1), r.start.0, Some(r.end.0))
-                    }
-                    SheetTarget::OpenRange(r) => {
-                        let sr = r.start.row.unwrap_or(0);
-                        let sc = r.start.col.unwrap_or(0);
-                        (sheet, sr, r.end.row, sc, r.end.col)
-                    }
-                    SheetTarget::Cell(c) => {
-                        (sheet, c.location.1, Some(c.location.1), c.location.0, Some(c.location.0))
-                    }
-                }
+        let range = RangeOption::parse(range_str.as_deref())?;
+
+        if cache && (filename.is_none() || filename.as_deref().is_some_and(is_glob)) {
+            return Err(Error::new_message("cache=1 requires a single filename"));
+        }
+        let shadow_table = |suffix: &str| {
+            format!(
+                "{}.{}",
+                quote_identifier(&args.database_name),
+                quote_identifier(&format!("{}_{suffix}", args.table_name))
+            )
+        };
+        let cache_table = cache.then(|| shadow_table("data"));
+        let cache_meta = cache.then(|| shadow_table("meta"));
+
+        // ── filename with wildcards: resolve columns from the first match ──
+        let file_glob = match &filename {
+            Some(f) if is_glob(f) => {
+                let first = expand_glob(f)?.into_iter().next().ok_or_else(|| {
+                    Error::new_message(format!("no files match '{f}'"))
+                })?;
+                let pattern = f.clone();
+                filename = Some(first);
+                Some(pattern)
             }
-            None => (None, 0, None, 0, None),
+            _ => None,
         };
 
-        if filename.is_some() {
+        if let Some(file) = filename {
             // ── filename provided: resolve columns at CREATE time ──
-            let data = std::fs::read(filename.as_ref().unwrap())
-                .map_err(|e| Error::new_message(format!("cannot read '{}': {e}", filename.as_ref().unwrap())))?;
-            let mut workbook =
-                calamine::open_workbook_auto_from_rs(std::io::Cursor::new(data))
-                    .map_err(|e| Error::new_message(format!("cannot open workbook: {e}")))?;
-
-            let sheet = match &sheet_name {
-                Some(name) => name.clone(),
-                None => workbook.sheet_names().first().unwrap().clone(),
-            };
-
-            let worksheet = workbook
-                .worksheet_range(&sheet)
-                .map_err(|_| Error::new_message(format!("sheet '{sheet}' not found")))?;
-
-            let actual_end_col = end_col.unwrap_or_else(|| {
-                worksheet
-                    .rows()
-                    .skip(start_row as usize)
-                    .map(|r| r.len() as u32)
-                    .max()
-                    .unwrap_or(0)
-                    .saturating_sub(1)
-            });
-            let num_columns = (actual_end_col - start_col + 1) as usize;
-
-            let (create_sql, declared_types) = build_create_sql(
-                &explicit_columns, has_headers, num_columns,
-                start_col, actual_end_col, &worksheet, start_row, None,
-            )?;
-
-            let vtab = XL0Table {
-                base: unsafe { mem::zeroed() },
-                filename,
-                sheet_name: Some(sheet),
-                start_row,
-                end_row,
-                start_col,
-                end_col: Some(actual_end_col),
-                num_columns,
-                has_headers,
-                declared_types,
-                source_column_idx: None,
+            let data = std::fs::read(&file)
+                .map_err(|e| Error::new_message(format!("cannot read '{file}': {e}")))?;
+            let fingerprint = file_glob.is_none().then(|| Fingerprint::new(&file, &data, None));
+            let data = decrypt_with(data, password.as_deref())?;
+            // with a glob, each file falls back to its own first sheet
+            let glob_sheet = file_glob.as_ref().map(|_| range.sheet_name.clone());
+            let source_suffix = file_glob
+                .as_ref()
+                .map(|_| format!(", {} hidden, {} hidden", GLOB_COLUMNS[0], GLOB_COLUMNS[1]));
+            let layout = LayoutOptions {
+                range,
+                header_rows,
+                header_case,
+                infer_types,
+                stop_at_blank,
+                skip_blank_rows,
+                explicit_columns,
             };
+            let (sheet, worksheet) = open_worksheet(data, layout.range.sheet_name.as_deref())?;
+            let (create_sql, mut vtab) =
+                Self::from_worksheet(db, sheet, &worksheet, &layout, source_suffix.as_deref())?;
+            vtab.filename = Some(file);
+            if let Some(sheet) = glob_sheet {
+                vtab.sheet_name = sheet;
+                vtab.file_column_idx = Some(vtab.num_columns);
+            }
+            vtab.file_glob = file_glob;
+            vtab.fingerprint = fingerprint;
+            vtab.on_change = on_change;
+            vtab.password = password;
+            vtab.cache_table = cache_table;
+            vtab.cache_meta = cache_meta;
             Ok((create_sql, vtab))
         } else {
             // ── no filename: require explicit columns, add hidden "source" column ──
-            if explicit_columns.is_empty() && !has_headers {
+            if range.auto {
+                return Err(Error::new_message("range=auto requires filename"));
+            }
+            let RangeOption { sheet_name, start_row, end_row, start_col, end_col, .. } = range;
+            let header_rows = header_rows.unwrap_or(0);
+            if explicit_columns.is_empty() && header_rows == 0 {
                 return Err(Error::new_message(
                     "either filename or explicit column declarations are required"
                 ));
@@ -237,26 +501,73 @@ impl<'vtab> VTab<'vtab> for XL0Table {
 
             let vtab = XL0Table {
                 base: unsafe { mem::zeroed() },
+                db,
                 filename: None,
+                file_glob: None,
                 sheet_name,
                 start_row,
                 end_row,
                 start_col,
-                end_col: Some(actual_end_col),
+                range_end_col: end_col,
+                columns: (start_col..=actual_end_col).map(Some).collect(),
+                header: Vec::new(),
+                fingerprint: None,
+                on_change,
+                password: None,
+                cache_table: None,
+                cache_meta: None,
+                column_names: explicit_columns.iter().map(|c| c.name.clone()).collect(),
+                pending: None,
                 num_columns,
-                has_headers,
+                header_rows,
                 declared_types: types,
+                stop_at_blank,
+                skip_blank_rows,
                 source_column_idx: Some(source_idx),
+                file_column_idx: None,
             };
             Ok((create_sql, vtab))
         }
     }
 
     fn destroy(&self) -> Result<()> {
+        for table in self.cache_table.iter().chain(&self.cache_meta) {
+            cache::execute(self.db, &format!("DROP TABLE IF EXISTS {table}"))?;
+        }
         Ok(())
     }
 
     fn best_index(&self, mut info: IndexInfo) -> core::result::Result<(), BestIndexError> {
+        if self.cache_table.is_some() {
+            // Cache mode: comparisons are passed on to the shadow table, where
+            // they can use its indexes. SQLite still double-checks them.
+            let mut terms: Vec<String> = Vec::new();
+            let mut has_eq = false;
+            for mut constraint in info.constraints() {
+                let col = constraint.column_idx();
+                if !constraint.usable() || col < 0 || col as usize >= self.num_columns {
+                    continue;
+                }
+                let op = match constraint.op() {
+                    Some(ConstraintOperator::EQ) => "=",
+                    Some(ConstraintOperator::GT) => ">",
+                    Some(ConstraintOperator::GE) => ">=",
+                    Some(ConstraintOperator::LT) => "<",
+                    Some(ConstraintOperator::LE) => "<=",
+                    _ => continue,
+                };
+                has_eq |= op == "=";
+                terms.push(format!("{col}{op}"));
+                constraint.set_argv_index(terms.len() as i32);
+            }
+            info.set_idxnum(IDX_CACHE);
+            info.set_idxstr(&terms.join(","))
+                .map_err(|_| BestIndexError::Error)?;
+            let cost = if has_eq { 10.0 } else if terms.is_empty() { 10000.0 } else { 1000.0 };
+            info.set_estimated_cost(cost);
+            info.set_estimated_rows(cost as i64);
+            return Ok(());
+        }
         if let Some(source_idx) = self.source_column_idx {
             // Source-at-query-time mode: require source column constraint
             let mut has_source = false;
@@ -274,9 +585,30 @@ impl<'vtab> VTab<'vtab> for XL0Table {
             if !has_source {
                 return Err(BestIndexError::Error);
             }
-            info.set_idxnum(1);
+            info.set_idxnum(IDX_SOURCE);
+        } else if let Some(file_idx) = self.file_column_idx {
+            // Glob mode: an equality constraint on _file limits which files are opened
+            let mut has_file = false;
+            for mut constraint in info.constraints() {
+                if constraint.column_idx() == file_idx as i32
+                    && constraint.usable()
+                    && constraint.op() == Some(ConstraintOperator::EQ)
+                    && !has_file
+                {
+                    constraint.set_omit(true);
+                    constraint.set_argv_index(1);
+                    has_file = true;
+                }
+            }
+            if has_file {
+                info.set_idxnum(IDX_FILE_CONSTRAINT);
+                info.set_estimated_cost(1000.0);
+                info.set_estimated_rows(1000);
+                return Ok(());
+            }
+            info.set_idxnum(IDX_FILENAME);
         } else {
-            info.set_idxnum(0);
+            info.set_idxnum(IDX_FILENAME);
         }
         info.set_estimated_cost(100000.0);
         info.set_estimated_rows(100000);
@@ -288,17 +620,609 @@ impl<'vtab> VTab<'vtab> for XL0Table {
     }
 }
 
-/// Build the CREATE TABLE SQL and declared_types from column declarations.
+impl XL0Table {
+    /// Error unless rows of this table can be written back to a workbook.
+    fn check_writable(&self) -> Result<()> {
+        if self.filename.is_none() || self.file_glob.is_some() || self.cache_table.is_some() {
+            return Err(Error::new_message(
+                "only xl0 tables over a single filename without cache=1 can be written",
+            ));
+        }
+        if self.password.is_some() {
+            return Err(Error::new_message("xl0 tables over encrypted workbooks are read-only"));
+        }
+        Ok(())
+    }
+
+    /// Read the table's current rows, ready to be edited.
+    fn load_pending(&mut self) -> Result<&mut PendingEdits> {
+        if self.pending.is_none() {
+            let file = self.filename.clone().unwrap();
+            let (sheet, worksheet) = self.open_checked(&file, read_file(&file)?)?;
+            let first_row = self.start_row + self.header_rows;
+            let table_rows = self.sheet_rows(&worksheet);
+            // every row from the first data row to the last one of the table,
+            // including blank rows that were skipped
+            let end = table_rows.last().map_or(first_row, |(row, _)| row + 1);
+            let original: Vec<Vec<Data>> = (first_row..end)
+                .map(|r| {
+                    let row_data = worksheet.rows().nth(r as usize).unwrap_or(&[]);
+                    self.row_cells(row_data)
+                })
+                .collect();
+            let rows = (first_row..).zip(original.iter().cloned()).collect();
+            self.pending = Some(PendingEdits {
+                sheet,
+                first_row,
+                original,
+                rows,
+                next_row: end,
+                used_rows: worksheet.end().map_or(0, |(row, _)| row + 1),
+            });
+        }
+        Ok(self.pending.as_mut().unwrap())
+    }
+
+    /// Write the edited rows back to the file. Rows that moved up after a
+    /// delete are rewritten, and cells left over at the end are cleared.
+    fn save_pending(&mut self) -> Result<()> {
+        let Some(pending) = self.pending.as_ref() else {
+            return Ok(());
+        };
+        let empty: Vec<Data> = Vec::new();
+        let mut edits = CellEdits::new();
+        for i in 0..pending.original.len().max(pending.rows.len()) {
+            let new = pending.rows.get(i).map_or(&empty, |(_, cells)| cells);
+            if pending.original.get(i).unwrap_or(&empty) == new {
+                continue;
+            }
+            let row = pending.first_row + i as u32;
+            for (k, col) in self.columns.iter().enumerate() {
+                if let Some(col) = col {
+                    edits.insert((row, *col), new.get(k).cloned().unwrap_or(Data::Empty));
+                }
+            }
+        }
+        if edits.is_empty() {
+            return Ok(());
+        }
+        let file = self.filename.as_ref().unwrap();
+        let data = patch::set_cells(&read_file(file)?, &pending.sheet, &edits)?;
+        // write next to the file, then swap it in
+        let tmp = format!("{file}.xl0-tmp");
+        std::fs::write(&tmp, data)
+            .and_then(|_| std::fs::rename(&tmp, file))
+            .map_err(|e| Error::new_message(format!("cannot write '{file}': {e}")))
+    }
+}
+
+impl<'vtab> VTabWriteable<'vtab> for XL0Table {
+    fn update(&'vtab mut self, operation: UpdateOperation<'_>, p_rowid: *mut sqlite3_int64) -> Result<()> {
+        self.check_writable()?;
+        let num_columns = self.num_columns;
+        let end_row = self.end_row;
+        let stop_at_blank = self.stop_at_blank;
+        let pending = self.load_pending()?;
+        match operation {
+            UpdateOperation::Delete(rowid) => {
+                let i = pending.position(api::value_int64(rowid))?;
+                pending.rows.remove(i);
+            }
+            UpdateOperation::Insert { values, .. } => {
+                let cells = values[..num_columns]
+                    .iter()
+                    .map(value_to_data)
+                    .collect::<Result<Vec<Data>>>()?;
+                let last_row = pending.first_row + pending.rows.len() as u32;
+                if end_row.is_some_and(|end| last_row > end) {
+                    return Err(Error::new_message("no room left in the range to insert a row"));
+                }
+                // the row after the table is the blank row that ends it, unless
+                // nothing follows it or a deleted row made room above it
+                let past_table = last_row >= pending.first_row + pending.original.len() as u32;
+                if stop_at_blank && past_table && last_row < pending.used_rows {
+                    return Err(Error::new_message(
+                        "cannot insert a row with stop_at_blank=1: it would fill the blank row that ends the table",
+                    ));
+                }
+                let row = pending.next_row;
+                pending.next_row += 1;
+                pending.rows.push((row, cells));
+                unsafe { *p_rowid = i64::from(row) + 1 };
+            }
+            UpdateOperation::Update { _values: values } => {
+                // The pinned sqlite-loadable rev passes xUpdate's whole argv:
+                // the old rowid, the new rowid, then one value per column (see
+                // https://www.sqlite.org/vtab.html#xupdate). Its 0.0.6-alpha.6
+                // release passes only the columns, so check the layout rather
+                // than shift every column by two.
+                if values.len() != num_columns + 2 {
+                    return Err(Error::new_message(format!(
+                        "unexpected UPDATE arguments: expected 2 rowids and {num_columns} columns, got {} values",
+                        values.len()
+                    )));
+                }
+                let rowid = api::value_int64(&values[0]);
+                if api::value_int64(&values[1]) != rowid {
+                    return Err(Error::new_message("the rowid of an xl0 row cannot be changed"));
+                }
+                let cells = values[2..2 + num_columns]
+                    .iter()
+                    .map(value_to_data)
+                    .collect::<Result<Vec<Data>>>()?;
+                let i = pending.position(rowid)?;
+                pending.rows[i].1 = cells;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<'vtab> VTabWriteableWithTransactions<'vtab> for XL0Table {
+    fn begin(&'vtab mut self) -> Result<()> {
+        self.pending = None;
+        Ok(())
+    }
+
+    fn sync(&'vtab mut self) -> Result<()> {
+        self.save_pending()
+    }
+
+    fn commit(&'vtab mut self) -> Result<()> {
+        self.pending = None;
+        Ok(())
+    }
+
+    fn rollback(&'vtab mut self) -> Result<()> {
+        self.pending = None;
+        Ok(())
+    }
+}
+
+/// How header text is turned into column names.
+#[derive(Clone, Copy, PartialEq)]
+enum HeaderCase {
+    /// Use the header text as-is.
+    Preserve,
+    /// Lowercase and join words with `_`, e.g. `Grade Level` → `grade_level`.
+    Snake,
+}
+
+/// Options that control how columns are named and typed when no explicit
+/// column declarations are given.
+struct SchemaOptions {
+    /// Number of rows at the top of the range used as column names.
+    header_rows: u32,
+    /// How header text is normalized into column names.
+    header_case: HeaderCase,
+    /// Number of data rows to sample when inferring column types (0 = off).
+    infer_types: usize,
+}
+
+/// Sheet and bounds given by the `range` option.
+struct RangeOption {
+    /// Detect the table region once the worksheet is loaded (`range=auto`)
+    auto: bool,
+    sheet_name: Option<String>,
+    start_row: u32,
+    end_row: Option<u32>,
+    start_col: u32,
+    end_col: Option<u32>,
+}
+
+impl RangeOption {
+    fn parse(range: Option<&str>) -> Result<RangeOption> {
+        let unbounded = |auto, sheet_name| RangeOption {
+            auto,
+            sheet_name,
+            start_row: 0,
+            end_row: None,
+            start_col: 0,
+            end_col: None,
+        };
+        let Some(range) = range else {
+            return Ok(unbounded(false, None));
+        };
+        // `range=auto` (or `Sheet!auto`)
+        if range.eq_ignore_ascii_case("auto") {
+            return Ok(unbounded(true, None));
+        }
+        if let Some(sheet) = range.strip_suffix("!auto") {
+            return Ok(unbounded(true, Some(sheet.trim_matches('\'').to_owned())));
+        }
+        let parsed = parse_sheet_reference(range)
+            .map_err(|e| Error::new_message(format!("invalid range: {e}")))?;
+        let (start_row, end_row, start_col, end_col) = match parsed.target {
+            SheetTarget::Range(r) => (r.start.1, Some(r.end.1), r.start.0, Some(r.end.0)),
+            SheetTarget::OpenRange(r) => (
+                r.start.row.unwrap_or(0),
+                r.end.row,
+                r.start.col.unwrap_or(0),
+                r.end.col,
+            ),
+            SheetTarget::Cell(c) => {
+                (c.location.1, Some(c.location.1), c.location.0, Some(c.location.0))
+            }
+        };
+        Ok(RangeOption {
+            auto: false,
+            sheet_name: parsed.sheet,
+            start_row,
+            end_row,
+            start_col,
+            end_col,
+        })
+    }
+}
+
+/// Where a table sits in a workbook, and how its columns are declared.
+struct LayoutOptions {
+    range: RangeOption,
+    /// `None` leaves it to `range=auto`, and otherwise means no header rows
+    header_rows: Option<u32>,
+    header_case: HeaderCase,
+    infer_types: usize,
+    stop_at_blank: bool,
+    skip_blank_rows: bool,
+    explicit_columns: Vec<ColumnDeclaration>,
+}
+
+impl LayoutOptions {
+    /// Layout of `range` with the xl0 defaults: no header rows and no type
+    /// inference.
+    fn new(range: Option<&str>) -> Result<LayoutOptions> {
+        Ok(LayoutOptions {
+            range: RangeOption::parse(range)?,
+            header_rows: None,
+            header_case: HeaderCase::Preserve,
+            infer_types: 0,
+            stop_at_blank: false,
+            skip_blank_rows: false,
+            explicit_columns: Vec::new(),
+        })
+    }
+
+    /// Layout for `xl_import()` from a JSON object of options, named like
+    /// the xl0 options.
+    fn from_json(range: Option<&str>, options: &Value) -> Result<LayoutOptions> {
+        LayoutOptions::new(range)?.with_json(options)
+    }
+
+    /// This layout with the options of a JSON object applied over it.
+    fn with_json(self, options: &Value) -> Result<LayoutOptions> {
+        let mut layout = self;
+        let options = match options {
+            Value::Object(options) => options,
+            Value::Null => return Ok(layout),
+            _ => return Err(Error::new_message("options must be a JSON object")),
+        };
+        let flag = |key: &str, value: &Value| {
+            value.as_bool().ok_or_else(|| Error::new_message(format!("{key} must be true or false")))
+        };
+        for (key, value) in options {
+            match key.as_str() {
+                "headers" | "header_rows" => {
+                    layout.header_rows = Some(match value {
+                        Value::Bool(b) => u32::from(*b),
+                        _ => value.as_u64().and_then(|n| u32::try_from(n).ok()).ok_or_else(|| {
+                            Error::new_message(format!("{key} must be a number of header rows"))
+                        })?,
+                    });
+                }
+                "header_case" => {
+                    layout.header_case = match value.as_str() {
+                        Some("preserve") => HeaderCase::Preserve,
+                        Some("snake_case") => HeaderCase::Snake,
+                        _ => {
+                            return Err(Error::new_message(format!(
+                                "unknown header_case {value}, expected 'preserve' or 'snake_case'"
+                            )))
+                        }
+                    };
+                }
+                "infer_types" => {
+                    layout.infer_types = value.as_u64().map(|n| n as usize).ok_or_else(|| {
+                        Error::new_message("infer_types must be a non-negative integer")
+                    })?;
+                }
+                "stop_at_blank" => layout.stop_at_blank = flag(key, value)?,
+                "skip_blank_rows" => layout.skip_blank_rows = flag(key, value)?,
+                other => return Err(Error::new_message(format!("unknown option: '{other}'"))),
+            }
+        }
+        Ok(layout)
+    }
+}
+
+/// Infer a declared column type from a sample of cell values.
+///
+/// Empty and error cells are ignored. Returns `None` when nothing could be
+/// inferred, leaving the column untyped.
+fn infer_column_type<'a>(values: impl Iterator<Item = &'a Data>) -> Option<&'static str> {
+    let mut inferred: Option<&'static str> = None;
+    for value in values {
+        let t = match value {
+            Data::Empty | Data::Error(_) => continue,
+            Data::Int(_) | Data::Bool(_) => "INTEGER",
+            Data::Float(v) => {
+                if v.fract() == 0.0 {
+                    "INTEGER"
+                } else {
+                    "REAL"
+                }
+            }
+            Data::DateTime(dt) => {
+                if dt.is_duration() || dt.as_f64() < 1.0 {
+                    "TEXT"
+                } else if dt.as_f64().fract() == 0.0 {
+                    "DATE"
+                } else {
+                    "DATETIME"
+                }
+            }
+            Data::DateTimeIso(s) => {
+                if s.contains('T') {
+                    "DATETIME"
+                } else {
+                    "DATE"
+                }
+            }
+            Data::String(_) | Data::DurationIso(_) => "TEXT",
+        };
+        inferred = Some(match (inferred, t) {
+            (None, t) => t,
+            (Some(prev), t) if prev == t => t,
+            (Some("INTEGER"), "REAL") | (Some("REAL"), "INTEGER") => "REAL",
+            (Some("DATE"), "DATETIME") | (Some("DATETIME"), "DATE") => "DATETIME",
+            _ => "TEXT",
+        });
+    }
+    inferred
+}
+
+/// Sample up to `options.infer_types` data rows and infer a type per column.
+fn infer_types(
+    options: &SchemaOptions,
+    num_columns: usize,
+    start_col: u32,
+    worksheet: &calamine::Range<Data>,
+    start_row: u32,
+    end_row: Option<u32>,
+) -> Vec<Option<String>> {
+    if options.infer_types == 0 {
+        return vec![None; num_columns];
+    }
+    let data_start = (start_row + options.header_rows) as usize;
+    let data_end = end_row.map(|er| er as usize + 1).unwrap_or(usize::MAX);
+    let sample: Vec<&[Data]> = worksheet
+        .rows()
+        .skip(data_start)
+        .take(options.infer_types.min(data_end.saturating_sub(data_start)))
+        .collect();
+    (0..num_columns)
+        .map(|i| {
+            let c = start_col as usize + i;
+            infer_column_type(sample.iter().filter_map(|row| row.get(c))).map(str::to_owned)
+        })
+        .collect()
+}
+
+/// Text of a header cell, or `None` if the cell is blank.
+///
+/// Numbers and dates are stringified (`2024`, `2024-01-15`) rather than
+/// replaced, so year or period headers keep their meaning.
+fn header_text(data: &Data) -> Option<String> {
+    let text = match data {
+        Data::String(s) | Data::DateTimeIso(s) | Data::DurationIso(s) => s.trim().to_owned(),
+        Data::Float(v) if v.fract() == 0.0 && v.abs() < 1e15 => format!("{}", *v as i64),
+        Data::Float(v) => v.to_string(),
+        Data::Int(v) => v.to_string(),
+        Data::Bool(v) => v.to_string(),
+        Data::DateTime(dt) => crate::xl_datetime_text(dt),
+        Data::Error(e) => e.to_string(),
+        Data::Empty => String::new(),
+    };
+    if text.is_empty() {
+        None
+    } else {
+        Some(text)
+    }
+}
+
+/// Combine a block of stacked header rows into one name per column.
+///
+/// Blank cells in every row except the last are forward-filled from the left,
+/// as long as the rows above them belong to the same group, so a merged
+/// `2024` spanning `Q1` and `Q2` yields `2024_Q1` and `2024_Q2`. Returns
+/// `None` for columns whose header cells are all blank.
+fn combine_header_rows(header_block: &[&[Data]], start_col: u32, end_col: u32) -> Vec<Option<String>> {
+    let width = (end_col - start_col + 1) as usize;
+    let last = header_block.len() - 1;
+    let mut filled: Vec<Vec<Option<String>>> = Vec::with_capacity(header_block.len());
+    for (r, row) in header_block.iter().enumerate() {
+        let mut cells: Vec<Option<String>> = (start_col..=end_col)
+            .map(|c| row.get(c as usize).and_then(header_text))
+            .collect();
+        if r < last {
+            for i in 1..width {
+                let same_group = filled.iter().all(|above| above[i] == above[i - 1]);
+                if cells[i].is_none() && same_group {
+                    cells[i] = cells[i - 1].clone();
+                }
+            }
+        }
+        filled.push(cells);
+    }
+    (0..width)
+        .map(|i| {
+            let mut parts: Vec<&str> = Vec::new();
+            for row in &filled {
+                if let Some(part) = &row[i] {
+                    if parts.last() != Some(&part.as_str()) {
+                        parts.push(part);
+                    }
+                }
+            }
+            if parts.is_empty() {
+                None
+            } else {
+                Some(parts.join("_"))
+            }
+        })
+        .collect()
+}
+
+/// Convert header text to `snake_case`: runs of non-alphanumeric characters
+/// become a single `_`, and a lower-to-upper transition starts a new word.
+fn to_snake_case(name: &str) -> String {
+    let mut out = String::new();
+    let mut prev_lower = false;
+    for c in name.chars() {
+        if c.is_alphanumeric() {
+            if c.is_uppercase() && prev_lower && !out.ends_with('_') {
+                out.push('_');
+            }
+            out.extend(c.to_lowercase());
+            prev_lower = c.is_lowercase() || c.is_numeric();
+        } else {
+            if !out.is_empty() && !out.ends_with('_') {
+                out.push('_');
+            }
+            prev_lower = false;
+        }
+    }
+    let trimmed = out.trim_end_matches('_');
+    if trimmed.is_empty() {
+        "_".to_owned()
+    } else {
+        trimmed.to_owned()
+    }
+}
+
+/// Names of the hidden columns of a table over a glob of files.
+const GLOB_COLUMNS: [&str; 2] = ["_file", "_sheet"];
+
+/// De-duplicate column names by appending `_2`, `_3`, ... to repeats, and to
+/// names that are `reserved` for other columns.
+/// SQLite column names are case-insensitive, so `Total` and `total` clash.
+fn dedupe_column_names(names: Vec<String>, reserved: &[&str]) -> Vec<String> {
+    let mut seen: std::collections::HashSet<String> =
+        reserved.iter().map(|name| name.to_lowercase()).collect();
+    names
+        .into_iter()
+        .map(|name| {
+            let mut candidate = name.clone();
+            let mut n = 2;
+            while !seen.insert(candidate.to_lowercase()) {
+                candidate = format!("{name}_{n}");
+                n += 1;
+            }
+            candidate
+        })
+        .collect()
+}
+
+/// Render a quoted column name with an optional declared type.
+fn column_sql(name: &str, declared_type: &Option<String>) -> String {
+    match declared_type {
+        Some(t) => format!("{} {t}", quote_identifier(name)),
+        None => quote_identifier(name),
+    }
+}
+
+/// Re-anchor a worksheet so that row and column indexes are absolute sheet
+/// positions, even when the used area doesn't start at `A1`.
+fn anchor_at_origin(worksheet: calamine::Range<Data>) -> calamine::Range<Data> {
+    match (worksheet.start(), worksheet.end()) {
+        (Some(start), Some(end)) if start != (0, 0) => worksheet.range((0, 0), end),
+        _ => worksheet,
+    }
+}
+
+/// Decrypt a workbook when a password is given.
+fn decrypt_with(data: Vec<u8>, password: Option<&str>) -> Result<Vec<u8>> {
+    match password {
+        Some(password) => crypto::decrypt(&data, password)
+            .map_err(|e| Error::new_message(format!("cannot decrypt workbook: {e}"))),
+        None => Ok(data),
+    }
+}
+
+/// Open a sheet of a workbook, or its first sheet, anchored at `A1`.
+fn open_worksheet(data: Vec<u8>, sheet_name: Option<&str>) -> Result<(String, Range<Data>)> {
+    let mut workbook =
+        Workbook::open(data)
+            .map_err(|e| Error::new_message(format!("cannot open workbook: {e}")))?;
+
+    let sheet_name = match sheet_name {
+        Some(name) => name.to_owned(),
+        None => workbook
+            .sheet_names()
+            .first()
+            .cloned()
+            .ok_or_else(|| Error::new_message("workbook has no sheets"))?,
+    };
+    let worksheet = workbook
+        .worksheet_range(&sheet_name)
+        .map_err(|_| Error::new_message(format!("sheet '{sheet_name}' not found")))?;
+    Ok((sheet_name, anchor_at_origin(worksheet)))
+}
+
+/// Index of the last column with a value at or below `start_row`.
+fn last_used_col(worksheet: &Range<Data>, start_row: u32) -> u32 {
+    worksheet
+        .rows()
+        .skip(start_row as usize)
+        .map(|r| r.len() as u32)
+        .max()
+        .unwrap_or(0)
+        .saturating_sub(1)
+}
+
+/// Combined header text of columns `start_col..=end_col`, all `None` when
+/// there are no header rows.
+fn read_header(
+    worksheet: &Range<Data>,
+    start_row: u32,
+    header_rows: u32,
+    start_col: u32,
+    end_col: u32,
+) -> Vec<Option<String>> {
+    let header_block: Vec<&[Data]> = worksheet
+        .rows()
+        .skip(start_row as usize)
+        .take(header_rows as usize)
+        .collect();
+    if header_block.is_empty() {
+        return vec![None; (end_col - start_col + 1) as usize];
+    }
+    combine_header_rows(&header_block, start_col, end_col)
+}
+
+/// Render header text for error messages, using column letters for blanks.
+fn describe_header(header: &[Option<String>], start_col: u32) -> String {
+    header
+        .iter()
+        .zip(start_col..)
+        .map(|(name, c)| name.clone().unwrap_or_else(|| column_idx_to_name(c)))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Build the CREATE TABLE SQL, column names and declared_types from column
+/// declarations.
+#[allow(clippy::too_many_arguments)]
 fn build_create_sql(
     explicit_columns: &[ColumnDeclaration],
-    has_headers: bool,
+    options: &SchemaOptions,
     num_columns: usize,
     start_col: u32,
     end_col: u32,
     worksheet: &calamine::Range<Data>,
     start_row: u32,
+    end_row: Option<u32>,
     source_suffix: Option<&str>,
-) -> Result<(String, Vec<Option<String>>)> {
+) -> Result<(String, Vec<String>, Vec<Option<String>>)> {
     let suffix = source_suffix.unwrap_or("");
 
     if !explicit_columns.is_empty() {
@@ -318,41 +1242,51 @@ fn build_create_sql(
         let cols_sql: Vec<String> =
             explicit_columns.iter().map(|c| c.vtab_declaration()).collect();
         let sql = format!("CREATE TABLE x({}{})", cols_sql.join(", "), suffix);
-        Ok((sql, types))
-    } else if has_headers {
-        let header_row = worksheet
+        let names = explicit_columns.iter().map(|c| c.name.clone()).collect();
+        Ok((sql, names, types))
+    } else if options.header_rows > 0 {
+        let header_block: Vec<&[Data]> = worksheet
             .rows()
-            .nth(start_row as usize)
-            .ok_or_else(|| Error::new_message("header row is out of range"))?;
-        let names: Vec<String> = (start_col..=end_col)
-            .map(|c| {
-                header_row
-                    .get(c as usize)
-                    .and_then(|d| match d {
-                        Data::String(s) => Some(s.clone()),
-                        _ => None,
-                    })
-                    .unwrap_or_else(|| column_idx_to_name(c))
+            .skip(start_row as usize)
+            .take(options.header_rows as usize)
+            .collect();
+        if header_block.is_empty() {
+            return Err(Error::new_message("header row is out of range"));
+        }
+        let names: Vec<String> = combine_header_rows(&header_block, start_col, end_col)
+            .into_iter()
+            .zip(start_col..=end_col)
+            .map(|(name, c)| {
+                let name = name.unwrap_or_else(|| column_idx_to_name(c));
+                match options.header_case {
+                    HeaderCase::Preserve => name,
+                    HeaderCase::Snake => to_snake_case(&name),
+                }
             })
             .collect();
-        let types = vec![None; num_columns];
+        // with a glob, headers can't take the names of the hidden columns
+        let reserved: &[&str] = if source_suffix.is_some() { &GLOB_COLUMNS } else { &[] };
+        let names = dedupe_column_names(names, reserved);
+        let types = infer_types(options, num_columns, start_col, worksheet, start_row, end_row);
         let cols_sql: Vec<String> = names
             .iter()
-            .map(|name| format!("'{}'", name.replace('\'', "''")))
+            .zip(&types)
+            .map(|(name, t)| column_sql(name, t))
             .collect();
         let sql = format!("CREATE TABLE x({}{})", cols_sql.join(", "), suffix);
-        Ok((sql, types))
+        Ok((sql, names, types))
     } else {
         let names: Vec<String> = (start_col..=end_col)
             .map(column_idx_to_name)
             .collect();
-        let types = vec![None; num_columns];
+        let types = infer_types(options, num_columns, start_col, worksheet, start_row, end_row);
         let cols_sql: Vec<String> = names
             .iter()
-            .map(|name| format!("'{}'", name.replace('\'', "''")))
+            .zip(&types)
+            .map(|(name, t)| column_sql(name, t))
             .collect();
         let sql = format!("CREATE TABLE x({}{})", cols_sql.join(", "), suffix);
-        Ok((sql, types))
+        Ok((sql, names, types))
     }
 }
 
@@ -360,7 +1294,14 @@ fn build_create_sql(
 pub struct XL0Cursor {
     base: sqlite3_vtab_cursor,
     rowid: i64,
-    rows: Option<Vec<Vec<Data>>>,
+    /// (index into `sources`, 0-based sheet row, row cells)
+    rows: Option<Vec<(usize, u32, Vec<Data>)>>,
+    /// (file, sheet) that each row was read from
+    sources: Vec<(String, String)>,
+    /// Query over the shadow table in cache mode
+    cached: Option<Statement>,
+    /// Whether `cached` is positioned on a row
+    cached_row: bool,
 }
 
 impl XL0Cursor {
@@ -370,69 +1311,331 @@ impl XL0Cursor {
             base,
             rowid: 0,
             rows: None,
+            sources: Vec::new(),
+            cached: None,
+            cached_row: false,
         }
     }
 }
 
-impl VTabCursor for XL0Cursor {
-    fn filter(
-        &mut self,
-        idx_num: c_int,
-        _idx_str: Option<&str>,
-        values: &[*mut sqlite3_value],
-    ) -> Result<()> {
-        let vtab = unsafe { &*(self.base.pVtab as *const XL0Table) };
-
-        let data = if idx_num == 1 {
-            // Source provided at query time via hidden column
-            api::value_blob(values.first().expect("source argument is required")).to_vec()
-        } else {
-            // Read from filename set at CREATE time
-            std::fs::read(vtab.filename.as_ref().unwrap())
-                .map_err(|e| Error::new_message(format!("cannot read '{}': {e}", vtab.filename.as_ref().unwrap())))?
-        };
+impl XL0Table {
+    /// Declare a table over `sheet`, resolving its bounds and columns. The
+    /// table reads no file until one is set.
+    fn from_worksheet(
+        db: *mut sqlite3,
+        sheet: String,
+        worksheet: &Range<Data>,
+        layout: &LayoutOptions,
+        source_suffix: Option<&str>,
+    ) -> Result<(String, XL0Table)> {
+        let RangeOption { mut start_row, mut end_row, mut start_col, mut end_col, .. } =
+            layout.range;
+        let mut header_rows = layout.header_rows;
+        if layout.range.auto {
+            let table = detect_table(worksheet).ok_or_else(|| {
+                Error::new_message(format!("no table region detected in sheet '{sheet}'"))
+            })?;
+            start_col = table.start.0;
+            start_row = table.start.1;
+            end_col = Some(table.end.0);
+            end_row = Some(table.end.1);
+            header_rows = header_rows.or(Some(u32::from(table.has_header)));
+        }
+        let header_rows = header_rows.unwrap_or(0);
 
-        let mut workbook =
-            calamine::open_workbook_auto_from_rs(std::io::Cursor::new(data))
-                .map_err(|e| Error::new_message(format!("cannot open workbook: {e}")))?;
+        let actual_end_col = end_col.unwrap_or_else(|| last_used_col(worksheet, start_row));
+        let num_columns = (actual_end_col - start_col + 1) as usize;
 
-        let sheet_name = match vtab.sheet_name.as_ref() {
-            Some(name) => name.clone(),
-            None => workbook.sheet_names().first().unwrap().clone(),
+        let options = SchemaOptions {
+            header_rows,
+            header_case: layout.header_case,
+            infer_types: layout.infer_types,
         };
-        let worksheet = workbook
-            .worksheet_range(&sheet_name)
-            .map_err(|_| Error::new_message(format!("sheet '{sheet_name}' not found")))?;
+        let (create_sql, column_names, declared_types) = build_create_sql(
+            &layout.explicit_columns, &options, num_columns,
+            start_col, actual_end_col, worksheet, start_row, end_row, source_suffix,
+        )?;
 
-        let data_start = if vtab.has_headers {
-            vtab.start_row as usize + 1
-        } else {
-            vtab.start_row as usize
+        let vtab = XL0Table {
+            base: unsafe { mem::zeroed() },
+            db,
+            filename: None,
+            file_glob: None,
+            sheet_name: Some(sheet),
+            start_row,
+            end_row,
+            start_col,
+            range_end_col: end_col,
+            columns: (start_col..=actual_end_col).map(Some).collect(),
+            header: read_header(worksheet, start_row, header_rows, start_col, actual_end_col),
+            fingerprint: None,
+            on_change: OnChange::Error,
+            password: None,
+            cache_table: None,
+            cache_meta: None,
+            column_names,
+            pending: None,
+            num_columns,
+            header_rows,
+            declared_types,
+            stop_at_blank: layout.stop_at_blank,
+            skip_blank_rows: layout.skip_blank_rows,
+            source_column_idx: None,
+            file_column_idx: None,
         };
+        Ok((create_sql, vtab))
+    }
 
-        let start_col = vtab.start_col as usize;
-        let end_col = vtab.end_col.unwrap() as usize;
+    /// Read the data rows of the configured range from a workbook.
+    /// Returns the sheet name that was read along with the rows.
+    fn read_rows(&self, data: Vec<u8>) -> Result<(String, SheetRows)> {
+        let (sheet_name, worksheet) = self.open_sheet(data)?;
+        Ok((sheet_name, self.sheet_rows(&worksheet)))
+    }
+
+    /// Open the configured sheet of a workbook, anchored at `A1`.
+    fn open_sheet(&self, data: Vec<u8>) -> Result<(String, Range<Data>)> {
+        open_worksheet(decrypt_with(data, self.password.as_deref())?, self.sheet_name.as_deref())
+    }
+
+    /// Data rows of the configured range with their 0-based sheet row, one
+    /// cell per declared column.
+    fn sheet_rows(&self, worksheet: &Range<Data>) -> SheetRows {
+        let data_start = (self.start_row + self.header_rows) as usize;
 
         let all_rows: Vec<&[Data]> = worksheet.rows().collect();
 
-        let end_idx = vtab
+        let end_idx = self
             .end_row
             .map(|er| (er as usize) + 1)
             .unwrap_or(all_rows.len());
 
-        let mut rows: Vec<Vec<Data>> = Vec::new();
-        for row_idx in data_start..end_idx {
-            if let Some(row_data) = all_rows.get(row_idx) {
-                let cells: Vec<Data> = (start_col..=end_col)
-                    .map(|c| {
-                        row_data
-                            .get(c)
-                            .cloned()
-                            .unwrap_or(Data::Empty)
+        self.without_blank_rows(
+            (data_start..end_idx)
+                .filter_map(|row_idx| Some((row_idx as u32, self.row_cells(all_rows.get(row_idx)?)))),
+        )
+    }
+
+    /// Apply `stop_at_blank` and `skip_blank_rows` to the rows of the table.
+    fn without_blank_rows(&self, table_rows: impl IntoIterator<Item = (u32, Vec<Data>)>) -> SheetRows {
+        let mut rows: SheetRows = Vec::new();
+        for (row, cells) in table_rows {
+            if crate::is_blank_row(&cells) {
+                if self.stop_at_blank {
+                    break;
+                }
+                if self.skip_blank_rows {
+                    continue;
+                }
+            }
+            rows.push((row, cells));
+        }
+        rows
+    }
+
+    /// Cells of a sheet row, one per declared column.
+    fn row_cells(&self, row_data: &[Data]) -> Vec<Data> {
+        self.columns
+            .iter()
+            .map(|c| {
+                c.and_then(|c| row_data.get(c as usize))
+                    .cloned()
+                    .unwrap_or(Data::Empty)
+            })
+            .collect()
+    }
+
+    /// Open the configured sheet of `file`. When the file's contents changed
+    /// since it was last read, check that its header still fits the schema.
+    fn open_checked(&mut self, file: &str, data: Vec<u8>) -> Result<(String, Range<Data>)> {
+        let fingerprint = self
+            .fingerprint
+            .as_ref()
+            .map(|previous| (previous.hash.clone(), Fingerprint::new(file, &data, Some(previous))));
+        let (sheet, worksheet) = self.open_sheet(data)?;
+        if let Some((previous_hash, fingerprint)) = fingerprint {
+            if fingerprint.hash != previous_hash {
+                self.revalidate(file, &worksheet)?;
+            }
+            self.fingerprint = Some(fingerprint);
+        }
+        Ok((sheet, worksheet))
+    }
+
+    /// Copy the sheet into the shadow table, unless the fingerprint saved
+    /// with the copy shows that the file hasn't changed since.
+    fn refresh_cache(&mut self) -> Result<()> {
+        let (Some(file), Some(table), Some(meta)) =
+            (self.filename.clone(), self.cache_table.clone(), self.cache_meta.clone())
+        else {
+            return Ok(());
+        };
+        let columns: Vec<String> = self
+            .column_names
+            .iter()
+            .zip(&self.declared_types)
+            .map(|(name, t)| column_sql(name, t))
+            .collect();
+        cache::execute(
+            self.db,
+            &format!("CREATE TABLE IF NOT EXISTS {table}({})", columns.join(", ")),
+        )?;
+        cache::execute(self.db, &format!("CREATE TABLE IF NOT EXISTS {meta}(modified, size, hash)"))?;
+        let saved = Fingerprint::load(self.db, &meta)?;
+        if saved.as_ref().is_some_and(|f| f.metadata_matches(&file)) {
+            return Ok(());
+        }
+        let data = read_file(&file)?;
+        let fingerprint = Fingerprint::new(&file, &data, saved.as_ref());
+        // a file that was only touched keeps its copy
+        if saved.as_ref().map(|f| &f.hash) != Some(&fingerprint.hash) {
+            let (_, worksheet) = self.open_checked(&file, data)?;
+            let rows: Vec<Vec<Data>> =
+                self.sheet_rows(&worksheet).into_iter().map(|(_, r)| r).collect();
+            cache::load(self.db, &table, self.num_columns, &rows)?;
+        }
+        fingerprint.store(self.db, &meta)
+    }
+
+    /// Compare the header of a changed file against the one the schema was
+    /// declared from. On drift, either fail or find each declared column
+    /// again by its header text.
+    fn revalidate(&mut self, file: &str, worksheet: &Range<Data>) -> Result<()> {
+        let sheet_end = last_used_col(worksheet, self.start_row).max(self.start_col);
+        let end_col = self.range_end_col.unwrap_or(sheet_end);
+        let header = read_header(worksheet, self.start_row, self.header_rows, self.start_col, end_col);
+        if header == self.header {
+            // the columns may have been remapped while the header differed
+            self.columns = (self.start_col..).take(self.columns.len()).map(Some).collect();
+            return Ok(());
+        }
+        match self.on_change {
+            OnChange::Error => Err(Error::new_message(format!(
+                "schema drift in '{file}': columns changed from ({}) to ({}), re-create the table or use on_change=rebuild",
+                describe_header(&self.header, self.start_col),
+                describe_header(&header, self.start_col),
+            ))),
+            OnChange::Rebuild => {
+                // Search the whole header row, so columns moved past the range are found.
+                let wide = read_header(
+                    worksheet,
+                    self.start_row,
+                    self.header_rows,
+                    self.start_col,
+                    sheet_end.max(end_col),
+                );
+                let mut used = vec![false; wide.len()];
+                let columns = self
+                    .header
+                    .iter()
+                    .map(|name| {
+                        let pos = (0..wide.len()).find(|&i| !used[i] && &wide[i] == name)?;
+                        used[pos] = true;
+                        Some(self.start_col + pos as u32)
                     })
                     .collect();
-                rows.push(cells);
+                // The schema can't be declared again once the table exists, so
+                // new columns within the range would silently go missing.
+                let added: Vec<&str> = (0..=(end_col - self.start_col) as usize)
+                    .filter(|&i| !used.get(i).copied().unwrap_or(true))
+                    .filter_map(|i| wide[i].as_deref())
+                    .collect();
+                if !added.is_empty() {
+                    return Err(Error::new_message(format!(
+                        "schema drift in '{file}': new columns ({}) are not in the table, re-create it to add them",
+                        added.join(", ")
+                    )));
+                }
+                self.columns = columns;
+                Ok(())
+            }
+        }
+    }
+}
+
+fn read_file(path: &str) -> Result<Vec<u8>> {
+    std::fs::read(path).map_err(|e| Error::new_message(format!("cannot read '{path}': {e}")))
+}
+
+impl VTabCursor for XL0Cursor {
+    fn filter(
+        &mut self,
+        idx_num: c_int,
+        idx_str: Option<&str>,
+        values: &[*mut sqlite3_value],
+    ) -> Result<()> {
+        let vtab = unsafe { &mut *(self.base.pVtab as *mut XL0Table) };
+
+        if idx_num == IDX_CACHE {
+            vtab.refresh_cache()?;
+            let table = vtab.cache_table.as_ref().unwrap();
+            let mut terms: Vec<String> = Vec::new();
+            for term in idx_str.unwrap_or("").split(',').filter(|t| !t.is_empty()) {
+                let split = term.find(|c: char| !c.is_ascii_digit()).unwrap_or(term.len());
+                let (col, op) = term.split_at(split);
+                let col: usize = col
+                    .parse()
+                    .map_err(|_| Error::new_message(format!("invalid index term '{term}'")))?;
+                terms.push(format!("{} {op} ?", quote_identifier(&vtab.column_names[col])));
+            }
+            let mut sql = format!("SELECT * FROM {table}");
+            if !terms.is_empty() {
+                sql += &format!(" WHERE {}", terms.join(" AND "));
+            }
+            let stmt = Statement::prepare(vtab.db, &sql)?;
+            for (i, value) in values.iter().enumerate() {
+                stmt.bind_value(i as c_int + 1, value)?;
+            }
+            self.cached_row = stmt.step()?;
+            self.cached = Some(stmt);
+            self.rowid = 0;
+            return Ok(());
+        }
+        self.cached = None;
+
+        if let Some(pending) = &vtab.pending {
+            // within a transaction, queries see the rows as edited so far
+            let rows = vtab.without_blank_rows(pending.rows.iter().cloned());
+            self.sources = vec![(vtab.filename.clone().unwrap(), pending.sheet.clone())];
+            self.rows = Some(rows.into_iter().map(|(n, r)| (0, n, r)).collect());
+            self.rowid = 0;
+            return Ok(());
+        }
+
+        let files: Vec<String> = match (idx_num, &vtab.file_glob) {
+            (IDX_SOURCE, _) => Vec::new(),
+            (IDX_FILE_CONSTRAINT, Some(pattern)) => {
+                // only open the requested file, and only if it belongs to the glob
+                let file = api::value_text(values.first().expect("_file argument is required"))?;
+                let matches = glob::Pattern::new(pattern)
+                    .map(|p| p.matches_path(std::path::Path::new(file)))
+                    .unwrap_or(false);
+                if matches && std::path::Path::new(file).is_file() {
+                    vec![file.to_owned()]
+                } else {
+                    Vec::new()
+                }
             }
+            (_, Some(pattern)) => expand_glob(pattern)?,
+            (_, None) => vec![vtab.filename.clone().unwrap()],
+        };
+
+        let mut rows: Vec<(usize, u32, Vec<Data>)> = Vec::new();
+        self.sources.clear();
+        if idx_num == IDX_SOURCE {
+            // Source provided at query time via hidden column
+            let data =
+                api::value_blob(values.first().expect("source argument is required")).to_vec();
+            let (sheet, source_rows) = vtab.read_rows(data)?;
+            rows.extend(source_rows.into_iter().map(|(n, r)| (0, n, r)));
+            self.sources.push((String::new(), sheet));
+        }
+        for file in files {
+            let (sheet, worksheet) = vtab.open_checked(&file, read_file(&file)?)?;
+            let file_rows = vtab.sheet_rows(&worksheet);
+            let source = self.sources.len();
+            rows.extend(file_rows.into_iter().map(|(n, r)| (source, n, r)));
+            self.sources.push((file, sheet));
         }
 
         self.rows = Some(rows);
@@ -442,10 +1645,16 @@ impl VTabCursor for XL0Cursor {
 
     fn next(&mut self) -> Result<()> {
         self.rowid += 1;
+        if let Some(stmt) = &self.cached {
+            self.cached_row = stmt.step()?;
+        }
         Ok(())
     }
 
     fn eof(&self) -> bool {
+        if self.cached.is_some() {
+            return !self.cached_row;
+        }
         self.rows
             .as_ref()
             .unwrap()
@@ -456,18 +1665,36 @@ impl VTabCursor for XL0Cursor {
     fn column(&self, context: *mut sqlite3_context, i: c_int) -> Result<()> {
         let vtab = unsafe { &*(self.base.pVtab as *const XL0Table) };
 
+        if let Some(stmt) = &self.cached {
+            api::result_value(context, &stmt.column_value(i));
+            return Ok(());
+        }
+
         // If this is the hidden source column, return null
         if Some(i as usize) == vtab.source_column_idx {
             api::result_null(context);
             return Ok(());
         }
 
-        let row = self
+        let (source, _, row) = self
             .rows
             .as_ref()
             .unwrap()
             .get(self.rowid as usize)
             .unwrap();
+
+        // Hidden _file and _sheet columns in glob mode
+        if let Some(file_idx) = vtab.file_column_idx {
+            let (file, sheet) = &self.sources[*source];
+            if i as usize == file_idx {
+                api::result_text(context, file)?;
+                return Ok(());
+            } else if i as usize == file_idx + 1 {
+                api::result_text(context, sheet)?;
+                return Ok(());
+            }
+        }
+
         match row.get(i as usize) {
             Some(data) => {
                 let affinity = vtab.declared_types[i as usize]
@@ -481,7 +1708,104 @@ impl VTabCursor for XL0Cursor {
         Ok(())
     }
 
+    /// The sheet row number of the current row (1-based, as shown in Excel).
+    /// In cache mode rows are numbered in order instead.
     fn rowid(&self) -> Result<i64> {
-        Ok(self.rowid)
+        match self.rows.as_ref().and_then(|rows| rows.get(self.rowid as usize)) {
+            Some((_, row_number, _)) if self.cached.is_none() => Ok(i64::from(*row_number) + 1),
+            _ => Ok(self.rowid + 1),
+        }
+    }
+}
+
+/// Give a declared `CREATE TABLE x(...)` statement a real table name.
+fn named_create_sql(create_sql: &str, table: &str) -> String {
+    create_sql.replacen("CREATE TABLE x(", &format!("CREATE TABLE {table}("), 1)
+}
+
+/// Value of an optional text argument, `None` when missing or NULL.
+fn optional_text(value: Option<&*mut sqlite3_value>) -> Result<Option<&str>> {
+    match value {
+        Some(value) if !matches!(api::value_type(value), api::ValueType::Null) => {
+            Ok(Some(api::value_text(value)?))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// The JSON object of options passed to `xl_import()` or `xl_schema()`.
+fn json_options(value: Option<&*mut sqlite3_value>) -> Result<Value> {
+    match optional_text(value)? {
+        Some(options) => serde_json::from_str(options)
+            .map_err(|e| Error::new_message(format!("options must be JSON: {e}"))),
+        None => Ok(Value::Null),
     }
 }
+
+/// `xl_import(table_name, workbook, range [, options])`: create a table
+/// holding a sheet range, the way `xl0` would declare it, and fill it.
+/// Returns the number of rows inserted.
+pub fn xl_import(context: *mut sqlite3_context, values: &[*mut sqlite3_value]) -> Result<()> {
+    let table = quote_table_name(api::value_text(&values[0])?);
+    let data = api::value_blob(&values[1]).to_vec();
+    let range = optional_text(values.get(2))?;
+    let layout = LayoutOptions::from_json(range, &json_options(values.get(3))?)?;
+
+    let db = unsafe { sqlite3ext_context_db_handle(context) };
+    let (sheet, worksheet) = open_worksheet(data, layout.range.sheet_name.as_deref())?;
+    let (create_sql, vtab) = XL0Table::from_worksheet(db, sheet, &worksheet, &layout, None)?;
+    let create_sql = named_create_sql(&create_sql, &table);
+
+    let affinities: Vec<ColumnAffinity> = vtab
+        .declared_types
+        .iter()
+        .map(|t| t.as_deref().map_or(ColumnAffinity::Blob, ColumnAffinity::from_declared_type))
+        .collect();
+    let rows: Vec<Vec<Data>> = vtab
+        .sheet_rows(&worksheet)
+        .into_iter()
+        .map(|(_, row)| {
+            row.iter()
+                .zip(&affinities)
+                .map(|(data, affinity)| with_affinity(data, affinity).into_owned())
+                .collect()
+        })
+        .collect();
+
+    cache::execute(db, "SAVEPOINT xl_import")?;
+    let result = cache::execute(db, &create_sql)
+        .and_then(|_| cache::load(db, &table, vtab.num_columns, &rows));
+    if result.is_err() {
+        cache::execute(db, "ROLLBACK TO xl_import")?;
+    }
+    cache::execute(db, "RELEASE xl_import")?;
+    result?;
+    api::result_int64(context, rows.len() as i64);
+    Ok(())
+}
+
+/// `xl_schema(workbook, range [, table_name, options])`: the `CREATE TABLE`
+/// statement `xl0` declares for a sheet range with `options`. Unlike `xl0`,
+/// the defaults suit a migration: one header row (or the detected one with
+/// `range=auto`), snake_case column names, and types inferred from every
+/// data row.
+pub fn xl_schema(context: *mut sqlite3_context, values: &[*mut sqlite3_value]) -> Result<()> {
+    let data = api::value_blob(&values[0]).to_vec();
+    let mut layout = LayoutOptions::new(optional_text(values.get(1))?)?;
+    if !layout.range.auto {
+        layout.header_rows = Some(1);
+    }
+    layout.header_case = HeaderCase::Snake;
+    layout.infer_types = usize::MAX;
+    let layout = layout.with_json(&json_options(values.get(3))?)?;
+
+    let db = unsafe { sqlite3ext_context_db_handle(context) };
+    let (sheet, worksheet) = open_worksheet(data, layout.range.sheet_name.as_deref())?;
+    let table = match optional_text(values.get(2))? {
+        Some(name) => name.to_owned(),
+        None => to_snake_case(&sheet),
+    };
+    let (create_sql, _) = XL0Table::from_worksheet(db, sheet, &worksheet, &layout, None)?;
+    api::result_text(context, named_create_sql(&create_sql, &quote_identifier(&table)))?;
+    Ok(())
+}
@@ -70,6 +70,11 @@ select * from xl_cells(readfile('tests/sample-abc.xlsx'), 'A1:B3', 'bbb'); -- @s
 -- xl_cells: assignments sheet
 select * from xl_cells(readfile('tests/students.xlsx'), 'A1:D3', 'assignments'); -- @snap xl_cells_assignments
 
+-- xl_cells: several areas, possibly on different sheets
+select * from xl_cells(readfile('tests/students.xlsx'), 'students!B2:B3,assignments!B2:C2'); -- @snap xl_cells_areas
+select group_concat(value, ',') from xl_cells(readfile('tests/students.xlsx'), 'B2,D1', 'assignments'); -- 'Essay: Modern Poetry,max_score'
+select * from xl_cells(readfile('tests/students.xlsx'), 'A1,nope!B2'); -- error: sheet 'nope' not found
+
 -- xl_rows: sheet!range syntax with row bounds
 select
   xl_at(row, 'A') as student_id,
@@ -123,3 +128,412 @@ create virtual table temp.students_dates using xl0(
 );
 select id, name, enrollment_date, birth_date
 from temp.students_dates limit 3; -- @snap xl0_dates
+
+-- xl0: infer column types from the first N data rows
+create virtual table temp.students_inferred using xl0(
+  filename="tests/students.xlsx",
+  range="students!A1:F*",
+  headers=1,
+  infer_types=10
+);
+select name, type from pragma_table_info('students_inferred'); -- @snap xl0_infer_types_schema
+
+select id, grade_level, enrollment_date, typeof(id), typeof(enrollment_date)
+from temp.students_inferred limit 2; -- @snap xl0_infer_types
+
+-- xl0: numeric, date, blank and duplicate headers
+create virtual table temp.headers_raw using xl0(
+  filename="tests/headers.xlsx",
+  range="A1:G*",
+  headers=1
+);
+select name from pragma_table_info('headers_raw'); -- @snap xl0_header_names
+
+-- xl0: header_case=snake_case
+create virtual table temp.headers_snake using xl0(
+  filename="tests/headers.xlsx",
+  range="A1:G*",
+  headers=1,
+  header_case=snake_case
+);
+select * from temp.headers_snake; -- @snap xl0_header_snake_case
+
+-- xl0: stacked header rows combine into one name
+create virtual table temp.stacked using xl0(
+  filename="tests/headers.xlsx",
+  range="stacked!A1:E*",
+  headers=2
+);
+select * from temp.stacked; -- @snap xl0_header_rows
+
+-- headers also takes yes/no and on/off
+create virtual table temp.sales_yes using xl0(filename="tests/sales.csv", headers=yes);
+select count(*) from temp.sales_yes where region = 'North'; -- 1
+create virtual table temp.sales_maybe using xl0(filename="tests/sales.csv", headers=maybe); -- error: headers must be a number of header rows, true or false
+
+-- xl_detect_table: find the table below a title block
+select
+  t ->> 'sheet' as sheet,
+  t ->> 'range' as range,
+  t ->> 'header_row' as header_row,
+  t ->> 'rows' as rows,
+  t ->> 'columns' as columns
+from (select xl_detect_table(readfile('tests/report.xlsx')) as t); -- @snap xl_detect_table
+
+select xl_detect_table(readfile('tests/sample-abc.xlsx')); -- NULL
+
+-- xl0: range=auto uses the detected region and header row
+create virtual table temp.report using xl0(
+  filename="tests/report.xlsx",
+  range=auto
+);
+select * from temp.report; -- @snap xl0_auto_range
+
+-- xl_rows: row numbers of a sheet whose data starts at B5
+select group_concat(row_number, ',') from xl_rows(readfile('tests/offset.xlsx')); -- '5,6,7'
+select group_concat(row_number, ',') from xl_rows(readfile('tests/offset.xlsx'), 'offset!B6:C*'); -- '6,7'
+
+-- xl_rows: stop at the first blank row of the requested columns
+select row_number, row ->> 'B' as region
+from xl_rows(readfile('tests/report.xlsx'), 'report!B5:E*', 1); -- @snap xl_rows_stop_at_blank
+
+-- xl_rows: skip blank rows instead
+select row_number, row ->> 'B' as region
+from xl_rows(readfile('tests/report.xlsx'), 'report!B5:E*', 0, 1); -- @snap xl_rows_skip_blank_rows
+
+-- xl0: open-ended range that stops before the trailing note
+create virtual table temp.report_rows using xl0(
+  filename="tests/report.xlsx",
+  range="report!B4:E*",
+  headers=1,
+  stop_at_blank=1
+);
+select * from temp.report_rows; -- @snap xl0_stop_at_blank
+
+-- xl0: a glob of workbooks with the same layout, with hidden _file and _sheet columns
+create virtual table temp.regions using xl0(
+  filename="tests/regions/*.xlsx",
+  range="A2:B*",
+  headers=1
+);
+select region, amount, _file, _sheet from temp.regions; -- @snap xl0_glob
+
+select region, amount from temp.regions
+where _file = 'tests/regions/2024-q2.xlsx'; -- @snap xl0_glob_file
+
+select count(*) from temp.regions where _file = 'tests/report.xlsx'; -- 0
+
+-- a header named like a hidden column gets a suffix
+select writefile('dist/file-header-q1.xlsx', xl_set(readfile('tests/regions/2024-q1.xlsx'), 'B2', '_file')) > 0; -- 1
+create virtual table temp.file_header using xl0(
+  filename="dist/file-header-*.xlsx",
+  range="A2:B*",
+  headers=1
+);
+select group_concat(name, ',') from pragma_table_info('file_header', 'temp'); -- 'region,_file_2'
+select sum(_file_2) from temp.file_header where _file = 'dist/file-header-q1.xlsx'; -- 30.0
+
+-- xl0: notice when the file changes between queries
+select writefile('dist/sales.xlsx', readfile('tests/regions/2024-q1.xlsx')) > 0; -- 1
+create virtual table temp.sales using xl0(
+  filename="dist/sales.xlsx",
+  range="A2:B*",
+  headers=1
+);
+create virtual table temp.sales_rebuild using xl0(
+  filename="dist/sales.xlsx",
+  range="A2:B*",
+  headers=1,
+  on_change=rebuild
+);
+select sum(amount) from temp.sales; -- 30.0
+
+-- same header, new rows
+select writefile('dist/sales.xlsx', readfile('tests/regions/2024-q2.xlsx')) > 0; -- 1
+select sum(amount) from temp.sales; -- 70.0
+
+-- a column was inserted before amount
+select writefile('dist/sales.xlsx', readfile('tests/sales-units.xlsx')) > 0; -- 1
+select * from temp.sales; -- error: schema drift in 'dist/sales.xlsx': columns changed from (region, amount) to (region, units), re-create the table or use on_change=rebuild
+select * from temp.sales_rebuild; -- error: schema drift in 'dist/sales.xlsx': new columns (units) are not in the table, re-create it to add them
+
+-- with on_change=rebuild, columns that moved are found again by name
+select writefile('dist/sales.xlsx', xl_set(
+  readfile('tests/regions/2024-q2.xlsx'),
+  'A2', 'amount', 'B2', 'region', 'A3', 30, 'B3', 'North', 'A4', 40, 'B4', 'South'
+)) > 0; -- 1
+select * from temp.sales_rebuild; -- @snap xl0_on_change_rebuild
+
+-- back to the declared header, columns are read in place again
+select writefile('dist/sales.xlsx', readfile('tests/regions/2024-q2.xlsx')) > 0; -- 1
+select sum(amount) from temp.sales_rebuild; -- 70.0
+
+-- xl0: copy the sheet into a <name>_data shadow table with cache=1
+create virtual table temp.students_cached using xl0(
+  filename="tests/students.xlsx",
+  range="students!A1:F*",
+  headers=1,
+  cache=1
+);
+create index temp.students_cached_name on students_cached_data(name);
+select count(*) from temp.students_cached_data; -- 5
+select id, name, email from temp.students_cached where name = 'Bob Jones'; -- @snap xl0_cache
+
+-- the shadow table is reloaded when the file changes
+select writefile('dist/cached.xlsx', readfile('tests/regions/2024-q1.xlsx')) > 0; -- 1
+create virtual table temp.sales_cached using xl0(
+  filename="dist/cached.xlsx",
+  range="A2:B*",
+  headers=1,
+  cache=1
+);
+select sum(amount) from temp.sales_cached; -- 30.0
+select writefile('dist/cached.xlsx', readfile('tests/regions/2024-q2.xlsx')) > 0; -- 1
+select sum(amount) from temp.sales_cached; -- 70.0
+
+-- the file's fingerprint is saved in a <name>_meta shadow table
+select size = length(readfile('dist/cached.xlsx')) from temp.sales_cached_meta; -- 1
+select length(hash) from temp.sales_cached_meta; -- 64
+drop table temp.sales_cached;
+select count(*) from sqlite_temp_master where name like 'sales_cached%'; -- 0
+
+-- xl0: INSERT, UPDATE and DELETE write rows back into the workbook
+select writefile('dist/edit.xlsx', readfile('tests/regions/2024-q1.xlsx')) > 0; -- 1
+create virtual table temp.edit using xl0(
+  filename="dist/edit.xlsx",
+  range="A2:B*",
+  headers=1
+);
+insert into temp.edit values ('East', 25);
+update temp.edit set amount = 15 where region = 'North';
+delete from temp.edit where region = 'South';
+select rowid, region, amount from temp.edit; -- @snap xl0_write
+
+-- within a transaction, queries see the edits so far, and inserted rows
+-- keep their rowid until the transaction commits
+begin;
+insert into temp.edit values ('West', 5);
+select amount = 5 from temp.edit where region = 'West'; -- 1
+update temp.edit set amount = 6 where region = 'West';
+delete from temp.edit where region = 'North';
+insert into temp.edit values ('Central', 7);
+select group_concat(rowid || ':' || region, ', ') from temp.edit; -- '4:East, 5:West, 6:Central'
+select amount = 6 from temp.edit where region = 'West'; -- 1
+commit;
+select group_concat(rowid || ':' || region, ', ') from temp.edit; -- '3:East, 4:West, 5:Central'
+
+-- updating one column leaves the others as they were
+update temp.edit set amount = 26 where region = 'East';
+update temp.edit set region = 'Far West' where rowid = 4;
+select group_concat(rowid || ':' || region || '=' || amount, ', ') from temp.edit; -- '3:East=26.0, 4:Far West=6.0, 5:Central=7.0'
+
+-- with stop_at_blank=1, an insert can't fill the blank row before the note
+select writefile('dist/report-edit.xlsx', readfile('tests/report.xlsx')) > 0; -- 1
+create virtual table temp.report_edit using xl0(
+  filename="dist/report-edit.xlsx",
+  range="report!B4:E*",
+  headers=1,
+  stop_at_blank=1
+);
+insert into temp.report_edit values ('West', 1, 2, 3); -- error: cannot insert a row with stop_at_blank=1: it would fill the blank row that ends the table
+select count(*) from temp.report_edit; -- 3
+
+-- cells outside the range are kept
+select row ->> 'A' from xl_rows(readfile('dist/edit.xlsx')) where row_number = 1; -- '2024-q1'
+
+-- xl_export: build an .xlsx blob from query rows
+select writefile('dist/export.xlsx', xl_export('Sales', json_object(
+  'region', region, 'amount', amount, 'active', json(active), 'since', since
+))) > 0
+from (
+  select 'North' as region, 10.5 as amount, 'true' as active, '2024-01-15' as since
+  union all select 'South', 20, 'false', '2024-02-01 09:30:00'
+); -- 1
+select * from xl_sheets(readfile('dist/export.xlsx')); -- @snap xl_export_sheets
+select row_number, row ->> 'A', row ->> 'D'
+from xl_rows(readfile('dist/export.xlsx')); -- @snap xl_export_rows
+create virtual table temp.exported using xl0(
+  filename="dist/export.xlsx",
+  headers=1
+);
+select region, amount, active, since, typeof(amount), typeof(active)
+from temp.exported; -- @snap xl_export_xl0
+select xl_export('Sales', '"North"'); -- error: each row must be a JSON object or array
+select xl_export('Sales', 'North'); -- error: row must be JSON: expected value at line 1 column 1
+select xl_export('Sales', json_object('region', region)) from (select 'North' as region where 0); -- NULL
+
+-- xl_workbook: several sheets with header formatting
+select writefile('dist/report.xlsx', xl_workbook(json_array(
+  xl_sheet('Summary', json_array(json_object('regions', 2, 'total', 30)), json_object(
+    'bold_header', json('true'), 'freeze_header', json('true'), 'column_widths', json_array(12, 10)
+  )),
+  xl_sheet('Detail', json_array(
+    json_object('region', 'North', 'amount', 10),
+    json_object('region', 'South', 'amount', 20)
+  ))
+))) > 0; -- 1
+select * from xl_sheets(readfile('dist/report.xlsx')); -- @snap xl_workbook_sheets
+select row ->> 'A', row ->> 'B'
+from xl_rows(readfile('dist/report.xlsx'), 'Detail'); -- @snap xl_workbook_detail
+select xl_workbook('[]'); -- error: a workbook needs at least one sheet
+select xl_sheet('Summary', '[]', '[1]'); -- error: options must be a JSON object
+
+-- xl_set: change cells in a workbook blob
+select * from xl_cells(
+  xl_set(readfile('tests/students.xlsx'), 'assignments!B2', 'Essay: Free Verse', 'assignments!C3', null),
+  'A1:C3',
+  'assignments'
+); -- @snap xl_set
+select value from xl_cells(xl_set(readfile('tests/students.xlsx'), 'C2', 12), 'C2:C2'); -- 12.0
+select xl_set(readfile('tests/students.xlsx'), 'A1:B2', 1); -- error: 'A1:B2' is not a single cell
+select xl_set(readfile('tests/students.xlsx'), 'A1'); -- error: xl_set() takes a workbook followed by cell and value pairs
+select xl_set(readfile('tests/students.xlsx'), 'C2', 1e999); -- error: cannot write inf into C2: cells only hold finite numbers
+select xl_set(readfile('tests/encrypted.xlsx'), 'C2', 1); -- error: cannot write a workbook encrypted with a password
+
+-- xl_import: create and fill a table from a sheet range
+select xl_import('imported', readfile('tests/students.xlsx'), 'A1:D3', '{"headers": 1, "infer_types": 10}'); -- 2
+select * from imported; -- @snap xl_import
+select typeof(id), typeof(grade_level) from imported limit 1; -- @snap xl_import_types
+select xl_import('imported', readfile('tests/students.xlsx'), 'A1:D3'); -- error: table "imported" already exists
+select xl_import('other', readfile('tests/students.xlsx'), 'A1:D3', '{"header": 1}'); -- error: unknown option: 'header'
+attach database ':memory:' as aux;
+select xl_import('aux.imported', readfile('tests/students.xlsx'), 'A1:D4', '{"headers": 1}'); -- 3
+select count(*) from aux.imported; -- 3
+
+-- xl_schema: CREATE TABLE statement for a sheet range
+select xl_schema(readfile('tests/students.xlsx'), null); -- 'CREATE TABLE "students"("id" INTEGER, "name" TEXT, "grade_level" INTEGER, "email" TEXT, "enrollment_date" DATE, "birth_date" DATE)'
+select xl_schema(readfile('tests/headers.xlsx'), 'headers!A1:G3', 'regions'); -- 'CREATE TABLE "regions"("region" TEXT, "total" INTEGER, "total_2" INTEGER, "d" TEXT, "2024" REAL, "grade_level" INTEGER, "2024_01_15" INTEGER)'
+select xl_schema(readfile('tests/students.xlsx'), 'A1:C3', null, '{"header_case": "preserve", "infer_types": 0}'); -- 'CREATE TABLE "students"("id", "name", "grade_level")'
+select xl_schema(readfile('tests/students.xlsx'), 'nope!A1:B2'); -- error: sheet 'nope' not found
+
+-- CSV and TSV blobs read as a single sheet
+select * from xl_sheets(readfile('tests/sales.csv')); -- @snap xl_csv_sheets
+select row_number, xl_at(row, 'A'), xl_at(row, 'B'), xl_at(row, 'C')
+from xl_rows(readfile('tests/sales.csv')); -- @snap xl_csv_rows
+select value from xl_cells(readfile('tests/sales.tsv'), 'B2:B2'); -- 3
+select xl_valid(readfile('tests/sales.tsv')); -- 1
+create virtual table temp.sales_csv using xl0(
+  filename="tests/sales.csv",
+  headers=1,
+  infer_types=10
+);
+select region, amount, since, typeof(amount) from temp.sales_csv; -- @snap xl0_csv
+
+-- xl_format and xl_validate: what a blob is, and why it can't be opened
+select xl_format(readfile('tests/students.xlsx')); -- 'xlsx'
+select xl_format(readfile('tests/sales.tsv')); -- 'tsv'
+select xl_format(cast('hello' as blob)); -- NULL
+select xl_valid(cast('<html>
+<body>Hello, world</body>
+</html>' as blob)); -- 0
+select xl_format(X'0102'); -- NULL
+select xl_validate(readfile('tests/students.xlsx')); -- '{"valid":true,"format":"xlsx","sheets":3,"encrypted":false,"error":null}'
+select xl_validate(X'0102'); -- '{"valid":false,"format":null,"sheets":null,"encrypted":false,"error":"Cannot detect file format"}'
+
+-- encrypted workbooks: a clear error, or decrypt with the password
+select * from xl_sheets(readfile('tests/encrypted.xlsx')); -- error: cannot open workbook: workbook is encrypted with a password
+select xl_validate(readfile('tests/encrypted.xlsx')); -- '{"valid":false,"format":null,"sheets":null,"encrypted":true,"error":"workbook is encrypted with a password"}'
+select group_concat(name, ',') from xl_sheets(xl_decrypt(readfile('tests/encrypted.xlsx'), 'secret')); -- 'students,assignments,grades'
+select xl_decrypt(readfile('tests/encrypted.xlsx'), 'nope'); -- error: cannot decrypt workbook: wrong password
+select xl_decrypt(readfile('tests/students.xlsx'), 'secret'); -- error: cannot decrypt workbook: not an encrypted workbook
+create virtual table temp.students_encrypted using xl0(
+  filename="tests/encrypted.xlsx",
+  range="students!A1:D*",
+  headers=1,
+  password="secret"
+);
+select count(*) from temp.students_encrypted; -- 5
+
+-- R1C1 references
+select xl_r1c1_to_a1('R2C1'); -- 'A2'
+select xl_r1c1_to_a1('R2C1:R100C6'); -- 'A2:F100'
+select xl_r1c1_to_a1('R5C'); -- '5:5'
+select xl_r1c1_to_a1('C3:C5'); -- 'C:E'
+select xl_r1c1_to_a1('''My Sheet''!R1C1:R3C2'); -- '''My Sheet''!A1:B3'
+select xl_r1c1_to_a1('R[-1]C'); -- error: invalid R1C1 reference 'R[-1]C': invalid reference: relative R1C1 references are not supported: R[-1]C
+select value from xl_cells(readfile('tests/students.xlsx'), 'students!R2C2:R2C2'); -- 'Alice Chen'
+
+-- references are checked against Excel's limits, and reversed ranges are reordered
+select group_concat(value, ',') from xl_cells(readfile('tests/students.xlsx'), 'B3:B2'); -- 'Alice Chen,Bob Jones'
+select * from xl_cells(readfile('tests/students.xlsx'), 'A1:XFE2'); -- error: invalid range: column XFE is past the last column XFD
+select * from xl_cells(readfile('tests/students.xlsx'), 'A0:B2'); -- error: invalid range: row 0 is outside rows 1 to 1048576
+select xl_r1c1_to_a1('R1048577C1'); -- error: invalid R1C1 reference 'R1048577C1': row 1048577 is outside rows 1 to 1048576
+select xl_at(row, 'A1') from xl_rows(readfile('tests/students.xlsx')) limit 1; -- error: invalid column name 'A1'
+
+-- quoting sheet names for references
+select xl_quote_sheet('students'); -- 'students'
+select xl_quote_sheet('Bob''s Data'); -- '''Bob''''s Data'''
+select xl_quote_sheet('Q1'); -- '''Q1'''
+select xl_quote_sheet(name) || '!A1' from xl_sheets(readfile('tests/sample-abc.xlsx')) limit 1; -- 'aaa!A1'
+select xl_r1c1_to_a1('''Bob''''s Data''!R1C1'); -- '''Bob''''s Data''!A1'
+
+-- address helpers
+select xl_column_name(0); -- 'A'
+select xl_column_name(26); -- 'AA'
+select xl_column_name(16384); -- error: column index 16384 is outside 0 to 16383
+select xl_column_index('AA'); -- 26
+select xl_column_index('xfd'); -- 16383
+select xl_column_index('A1'); -- error: invalid column name 'A1'
+select xl_address(1, 2); -- 'B3'
+select xl_address(1, 2, 'Bob''s Data'); -- '''Bob''''s Data''!B3'
+select xl_address(1, 2, null, 1); -- '$B$3'
+select xl_address(0, -1); -- error: row index -1 is outside 0 to 1048575
+select xl_parse_range('Sheet1!B5:A1'); -- '{"sheet":"Sheet1","start":{"column":0,"row":0},"end":{"column":1,"row":4},"address":"A1:B5"}'
+select xl_parse_range('A2:D*'); -- '{"sheet":null,"start":{"column":0,"row":1},"end":{"column":3,"row":null},"address":"A2:D"}'
+select xl_parse_range('A1:'); -- error: invalid range 'A1:': invalid reference: empty bound
+select xl_address(r ->> '$.start.column', r ->> '$.start.row', r ->> '$.sheet')
+from (select xl_parse_range('''My Data''!C4:E9') as r); -- '''My Data''!C4'
+
+-- range arithmetic
+select xl_range_contains('A1:D10', 'B2'); -- 1
+select xl_range_contains('A1:D10', 'C9:E10'); -- 0
+select xl_range_contains('Sheet1!B:B', 'Sheet1!B500'); -- 1
+select xl_range_contains('Sheet1!A1:D10', 'Sheet2!B2'); -- 0
+select xl_range_intersect('A1:C3', 'B2:D4'); -- 'B2:C3'
+select xl_range_intersect('B:C', 'Data!2:3'); -- 'Data!B2:C3'
+select xl_range_intersect('A1:B2', 'C3:D4'); -- NULL
+select xl_offset('B2:D3', 2, 1); -- 'C4:E5'
+select xl_offset('Sheet1!B2', 2, 0, 5, 3); -- 'Sheet1!B4:D8'
+select xl_offset('A:A', 0, 2); -- 'C:C'
+select xl_offset('B2', -2, 0); -- error: offset range is outside the sheet
+select xl_offset('B2', 0, 0, 0); -- error: height must be at least 1, got 0
+select xl_range_size('B2:D6'); -- '{"rows":5,"columns":3,"cells":15}'
+select xl_range_size('C:C'); -- '{"rows":1048576,"columns":1,"cells":1048576}'
+select xl_range_size('A1:'); -- error: invalid range 'A1:': invalid reference: empty bound
+
+-- xl_extract: one value, or a small range as JSON
+select xl_extract(readfile('tests/students.xlsx'), 'students!B2'); -- 'Alice Chen'
+select xl_extract(readfile('tests/students.xlsx'), 'E2'); -- '2023-08-21'
+select typeof(xl_extract(readfile('tests/students.xlsx'), 'C2')); -- 'real'
+select xl_extract(readfile('tests/students.xlsx'), 'Z100'); -- NULL
+select xl_extract(readfile('tests/students.xlsx'), 'students!A1:B2'); -- '[["id","name"],[1.0,"Alice Chen"]]'
+select xl_extract(readfile('tests/sales.tsv'), 'A:B'); -- '[["region","units"],["North",3],["South",4]]'
+select xl_extract(readfile('tests/sales.csv'), 'C3:D4'); -- '[["2024-02-01",null],[null,null]]'
+select xl_extract(readfile('tests/students.xlsx'), 'nope!A1'); -- error: sheet 'nope' not found
+select xl_extract(readfile('tests/students.xlsx'), 'A1,B2'); -- error: invalid reference 'A1,B2': expected a single area, not a list of areas
+select xl_extract(readfile('tests/students.xlsx'), 'A1:XFD1048576'); -- error: range 'A1:XFD1048576' has 17179869184 cells, more than the limit of 100000; use xl_cells() instead
+
+-- xl_cells and xl_extract agree on addresses when data doesn't start at A1
+select group_concat(column_name || row_number || '=' || value, ',') from xl_cells(readfile('tests/offset.xlsx'), 'A4:C6'); -- 'B5=item,C5=qty,B6=bolts,C6=40.0'
+select xl_extract(readfile('tests/offset.xlsx'), 'B6'); -- 'bolts'
+select xl_extract(readfile('tests/offset.xlsx'), 'B5:C6'); -- '[["item","qty"],["bolts",40.0]]'
+
+-- a workbook without sheets has no first sheet to default to
+select xl_extract(readfile('tests/no-sheets.xlsx'), 'A1'); -- error: workbook has no sheets
+select * from xl_cells(readfile('tests/no-sheets.xlsx'), 'A1:B2'); -- error: workbook has no sheets
+select * from xl_rows(readfile('tests/no-sheets.xlsx')); -- error: workbook has no sheets
+
+-- xl_find: search cells by value
+select * from xl_find(readfile('tests/students.xlsx'), '100.0'); -- @snap xl_find_exact
+select sheet || '!' || address from xl_find(readfile('tests/students.xlsx'), 'Alice Chen'); -- 'students!B2'
+select group_concat(address, ',') from xl_find(readfile('tests/students.xlsx'), '2023-08-21', 'students'); -- 'E2,E4'
+select count(*) from xl_find(readfile('tests/students.xlsx'), '%@SCHOOL.EDU', 'students', 'like'); -- 5
+select group_concat(address, ',') from xl_find(readfile('tests/students.xlsx'), '[AB]*', 'students', 'glob'); -- 'B2,B3'
+select group_concat(value, ', ') from xl_find(readfile('tests/students.xlsx'), '^(Essay|Lab):', null, 'regex'); -- 'Essay: Modern Poetry, Lab: Chemical Reactions'
+select count(*) from xl_find(readfile('tests/students.xlsx'), 'alice chen'); -- 0
+select count(*) from xl_find where workbook = readfile('tests/students.xlsx') and pattern = 'Alice Chen'
+  and in_sheet = 'students' and in_sheet = 'grades'; -- 0
+select count(*) from xl_find where workbook = readfile('tests/students.xlsx') and pattern = 'Alice Chen'
+  and in_sheet = 'students' and in_sheet = 'students'; -- 1
+select * from xl_find(readfile('tests/students.xlsx'), 'x', null, 'fuzzy'); -- error: unknown mode 'fuzzy', expected 'exact', 'like', 'glob' or 'regex'
+select * from xl_find(readfile('tests/students.xlsx'), 'x', 'nope'); -- error: sheet 'nope' not found
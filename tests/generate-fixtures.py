@@ -5,6 +5,7 @@
 
 import xlsxwriter
 import os
+import zipfile
 from datetime import datetime, date, time
 
 DIR = os.path.dirname(os.path.abspath(__file__))
@@ -96,4 +97,119 @@ for i, (sid, aid, score, submitted, spent) in enumerate(grades, 1):
 
 wb.close()
 
-print("Generated sample-abc.xlsx and students.xlsx")
+# ── headers.xlsx ──
+# duplicate, blank, numeric and date headers for xl0 column naming
+wb = xlsxwriter.Workbook(os.path.join(DIR, "headers.xlsx"))
+date_fmt = wb.add_format({"num_format": "yyyy-mm-dd"})
+
+ws = wb.add_worksheet("headers")
+ws.write_string(0, 0, "Region")
+ws.write_string(0, 1, "Total")
+ws.write_string(0, 2, "total")
+ws.write_number(0, 4, 2024)
+ws.write_string(0, 5, "Grade Level")
+ws.write_datetime(0, 6, date(2024, 1, 15), date_fmt)
+ws.write_row(1, 0, ["North", 10, 20, "x", 1.5, 9, 3])
+ws.write_row(2, 0, ["South", 30, 40, "y", 2.5, 10, 4])
+
+# two stacked header rows: a year over a quarter, with blank cells to forward-fill
+ws = wb.add_worksheet("stacked")
+ws.write_string(0, 0, "Region")
+ws.write_number(0, 1, 2024)
+ws.write_number(0, 3, 2025)
+ws.write_row(1, 1, ["Q1", "Q2", "Q1", "Q2"])
+ws.write_row(2, 0, ["North", 1, 2, 3, 4])
+ws.write_row(3, 0, ["South", 5, 6, 7, 8])
+
+wb.close()
+
+# ── report.xlsx ──
+# a table below a title block, followed by a note, for table detection
+wb = xlsxwriter.Workbook(os.path.join(DIR, "report.xlsx"))
+
+ws = wb.add_worksheet("report")
+ws.write_string(0, 0, "Quarterly Sales Report")
+ws.write_string(1, 0, "Generated 2024-04-01")
+ws.write_row(3, 1, ["region", "q1", "q2", "q3"])
+ws.write_row(4, 1, ["North", 10, 20, 30])
+ws.write_row(5, 1, ["South", 40, 50, 60])
+ws.write_row(6, 1, ["East", 70, 80, 90])
+ws.write_string(8, 1, "Note: figures in thousands")
+
+wb.close()
+
+# ── regions/*.xlsx ──
+# workbooks with the same layout below a title row, for reading a glob of files
+os.makedirs(os.path.join(DIR, "regions"), exist_ok=True)
+for name, rows in [
+    ("2024-q1", [("North", 10), ("South", 20)]),
+    ("2024-q2", [("North", 30), ("South", 40)]),
+]:
+    wb = xlsxwriter.Workbook(os.path.join(DIR, "regions", f"{name}.xlsx"))
+    ws = wb.add_worksheet("sales")
+    ws.write_string(0, 0, name)
+    ws.write_row(1, 0, ["region", "amount"])
+    for i, row in enumerate(rows):
+        ws.write_row(i + 2, 0, row)
+    wb.close()
+
+# ── sales-units.xlsx ──
+# the regions layout with an inserted "units" column, for schema drift
+wb = xlsxwriter.Workbook(os.path.join(DIR, "sales-units.xlsx"))
+ws = wb.add_worksheet("sales")
+ws.write_string(0, 0, "2024-q3")
+ws.write_row(1, 0, ["region", "units", "amount"])
+ws.write_row(2, 0, ["North", 3, 50])
+ws.write_row(3, 0, ["South", 4, 60])
+wb.close()
+
+# ── offset.xlsx ──
+# a table that starts at B5 rather than A1
+wb = xlsxwriter.Workbook(os.path.join(DIR, "offset.xlsx"))
+ws = wb.add_worksheet("offset")
+ws.write_row(4, 1, ["item", "qty"])
+ws.write_row(5, 1, ["bolts", 40])
+ws.write_row(6, 1, ["nuts", 25])
+wb.close()
+
+# ── no-sheets.xlsx ──
+# a workbook package whose workbook part lists no sheets, which xlsxwriter
+# won't write, so the parts are zipped directly
+NO_SHEETS_PARTS = {
+    "[Content_Types].xml": (
+        '<?xml version="1.0" encoding="UTF-8" standalone="yes"?>\n'
+        '<Types xmlns="http://schemas.openxmlformats.org/package/2006/content-types">'
+        '<Default Extension="rels" ContentType="application/vnd.openxmlformats-package.relationships+xml"/>'
+        '<Default Extension="xml" ContentType="application/xml"/>'
+        '<Override PartName="/xl/workbook.xml" '
+        'ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.sheet.main+xml"/>'
+        "</Types>"
+    ),
+    "_rels/.rels": (
+        '<?xml version="1.0" encoding="UTF-8" standalone="yes"?>\n'
+        '<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">'
+        '<Relationship Id="rId1" '
+        'Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/officeDocument" '
+        'Target="xl/workbook.xml"/>'
+        "</Relationships>"
+    ),
+    "xl/workbook.xml": (
+        '<?xml version="1.0" encoding="UTF-8" standalone="yes"?>\n'
+        '<workbook xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main" '
+        'xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships">'
+        "<sheets/></workbook>"
+    ),
+    "xl/_rels/workbook.xml.rels": (
+        '<?xml version="1.0" encoding="UTF-8" standalone="yes"?>\n'
+        '<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships"/>'
+    ),
+}
+with zipfile.ZipFile(os.path.join(DIR, "no-sheets.xlsx"), "w", zipfile.ZIP_DEFLATED) as z:
+    for name, xml in NO_SHEETS_PARTS.items():
+        z.writestr(zipfile.ZipInfo(name, (1980, 1, 1, 0, 0, 0)), xml)
+
+# encrypted.xlsx is students.xlsx encrypted with the password "secret"
+# (ECMA-376 agile encryption, AES-256/SHA-512). xlsxwriter can't encrypt, so
+# it is checked in rather than generated here.
+
+print("Generated sample-abc.xlsx, students.xlsx, headers.xlsx, report.xlsx, regions/*.xlsx, sales-units.xlsx, offset.xlsx and no-sheets.xlsx")